@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS idx_urls_last_scanned ON Urls (last_scanned ASC NULLS FIRST, created_at ASC);
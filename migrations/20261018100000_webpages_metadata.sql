@@ -0,0 +1,8 @@
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS description TEXT NOT NULL DEFAULT '';
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS lang TEXT;
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS canonical_url TEXT;
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS social_meta jsonb NOT NULL DEFAULT '{}';
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS headings jsonb NOT NULL DEFAULT '[]';
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS json_ld jsonb NOT NULL DEFAULT '[]';
+
+CREATE INDEX IF NOT EXISTS idx_webpages_lang ON Webpages (lang);
@@ -0,0 +1,2 @@
+-- the readable page text, used to build query biased snippets without unzipping compressed_body
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS body_text TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,23 @@
+-- the query language of /search filters on site:, title:, lang: and before:/after:,
+-- so the bm25 indexes need those fields next to the keywords
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS host TEXT
+    GENERATED ALWAYS AS (lower(substring(url from '^[a-zA-Z][a-zA-Z0-9+.-]*://(?:[^@/?#]*@)?([^:/?#]+)'))) STORED;
+ALTER TABLE MetaWebpages ADD COLUMN IF NOT EXISTS host TEXT
+    GENERATED ALWAYS AS (lower(substring(url from '^[a-zA-Z][a-zA-Z0-9+.-]*://(?:[^@/?#]*@)?([^:/?#]+)'))) STORED;
+
+DROP INDEX IF EXISTS webpages_url_keywords_idx;
+DROP INDEX IF EXISTS metawebpages_url_keywords_idx;
+
+CREATE INDEX IF NOT EXISTS idx_webpages_bm25 ON Webpages
+USING bm25 (url, keywords, title, host, lang, created_at)
+WITH (
+    key_field = 'url',
+    text_fields = '{"host": {"tokenizer": {"type": "keyword"}}, "lang": {"tokenizer": {"type": "keyword"}}}'
+);
+
+CREATE INDEX IF NOT EXISTS idx_meta_webpages_bm25 ON MetaWebpages
+USING bm25 (url, keywords, title, host, created_at)
+WITH (
+    key_field = 'url',
+    text_fields = '{"host": {"tokenizer": {"type": "keyword"}}}'
+);
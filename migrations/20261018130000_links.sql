@@ -0,0 +1,13 @@
+-- the page -> page edges found while parsing, the source of the page rank job
+CREATE TABLE IF NOT EXISTS Links (
+    source_url TEXT NOT NULL,
+    target_url TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (source_url, target_url)
+);
+
+CREATE INDEX IF NOT EXISTS idx_links_target_url ON Links (target_url);
+
+-- page rank scaled so the mean over the link graph is 1, pages outside of the graph keep 0
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS page_rank REAL NOT NULL DEFAULT 0;
@@ -0,0 +1,11 @@
+-- exact and near-duplicate detection, pages of one cluster are collapsed into one search result
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS content_hash TEXT;
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS simhash BIGINT;
+-- the simhash split into 4 bands of 16 bits tagged with their position, near-duplicates share a band
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS simhash_bands INTEGER[] NOT NULL DEFAULT '{}';
+-- url of the first page of the cluster, pages without one are their own cluster
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS cluster_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_webpages_content_hash ON Webpages (content_hash);
+CREATE INDEX IF NOT EXISTS idx_webpages_simhash_bands ON Webpages USING GIN (simhash_bands);
+CREATE INDEX IF NOT EXISTS idx_webpages_cluster_id ON Webpages (cluster_id);
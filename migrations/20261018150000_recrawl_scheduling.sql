@@ -0,0 +1,28 @@
+-- adaptive revisits, every fetch schedules the next one from how often the content changed
+ALTER TABLE Urls ADD COLUMN IF NOT EXISTS next_scan_at TIMESTAMP;
+ALTER TABLE Urls ADD COLUMN IF NOT EXISTS recrawl_interval_secs INTEGER;
+-- validators of the last response, sent back as a conditional GET
+ALTER TABLE Urls ADD COLUMN IF NOT EXISTS etag TEXT;
+ALTER TABLE Urls ADD COLUMN IF NOT EXISTS last_modified TEXT;
+ALTER TABLE Urls ADD COLUMN IF NOT EXISTS content_hash TEXT;
+ALTER TABLE Urls ADD COLUMN IF NOT EXISTS change_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE Urls ADD COLUMN IF NOT EXISTS unchanged_count INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_urls_next_scan_at ON Urls (next_scan_at) WHERE next_scan_at IS NOT NULL;
+
+ALTER TABLE Webpages ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP;
+
+-- replaced versions of changed pages, only filled when KEEP_PAGE_HISTORY is on
+CREATE TABLE IF NOT EXISTS WebpageVersions (
+    id BIGSERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    content_hash TEXT,
+    title TEXT NOT NULL,
+    keywords TEXT NOT NULL,
+    compressed_body BYTEA NOT NULL,
+    headers JSONB,
+    fetched_at TIMESTAMP NOT NULL,
+    replaced_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_webpage_versions_url ON WebpageVersions (url, replaced_at DESC);
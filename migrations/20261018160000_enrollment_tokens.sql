@@ -0,0 +1,14 @@
+-- workers authenticate with hmac signed tokens issued through the private api,
+-- the signature proves the token is ours and this table whether it is still valid
+CREATE TABLE IF NOT EXISTS EnrollmentTokens (
+    id UUID PRIMARY KEY,
+    label TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP,
+    revoked_at TIMESTAMP
+);
+
+-- the token a device first showed up with, revoking it locks out the device
+ALTER TABLE Devices ADD COLUMN IF NOT EXISTS enrollment_token_id UUID REFERENCES EnrollmentTokens(id);
+
+CREATE INDEX IF NOT EXISTS idx_devices_enrollment_token_id ON Devices (enrollment_token_id);
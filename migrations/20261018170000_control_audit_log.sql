@@ -0,0 +1,15 @@
+-- every mutation on the private control api, caller and role are null when no valid key was sent
+CREATE TABLE IF NOT EXISTS ControlAuditLog (
+    id BIGSERIAL PRIMARY KEY,
+    req_id UUID NOT NULL,
+    caller TEXT,
+    role TEXT,
+    method TEXT NOT NULL,
+    uri TEXT NOT NULL,
+    status SMALLINT NOT NULL,
+    ip TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_control_audit_log_created_at ON ControlAuditLog (created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_control_audit_log_caller ON ControlAuditLog (caller);
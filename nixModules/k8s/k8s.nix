@@ -33,6 +33,8 @@
           postgres_user = "ref+sops://sops_secrets.yaml?key=env.postgres_user";
           postgres_password = "ref+sops://sops_secrets.yaml?key=env.postgres_password";
           neo4j_auth = "ref+sops://sops_secrets.yaml?key=env.neo4j_auth";
+          worker_token_secret = "ref+sops://sops_secrets.yaml?key=env.worker_token_secret";
+          private_api_keys = "ref+sops://sops_secrets.yaml?key=env.private_api_keys";
         };
 
         _module.args = rec {
@@ -62,6 +64,16 @@
               key = "postgres_password";
             };
 
+            WORKER_TOKEN_SECRET.secretKeyRef = {
+              name = "db-creds";
+              key = "worker_token_secret";
+            };
+
+            PRIVATE_API_KEYS.secretKeyRef = {
+              name = "db-creds";
+              key = "private_api_keys";
+            };
+
             POSTGRES_DB = "Oxalate";
             DB_BIND_ADDRESS = "0.0.0.0";
             DB_DNS = "0.0.0.0";
@@ -1,13 +1,26 @@
-use axum::{Router, middleware::from_fn_with_state};
+use axum::{
+    Router,
+    middleware::{from_fn, from_fn_with_state},
+};
 use envconfig::Envconfig;
 use log::info;
 use neo4rs::Graph;
 use oxalate_env::load_env_vars;
-use oxalate_init::{init_kafka_producer, init_logger, init_neo4j_pool, init_postgres_pool};
+use oxalate_init::{
+    init_kafka_producer, init_logger, init_neo4j_pool, init_postgres_pool, init_tracer,
+};
 // use oxalate_env::ENVVARS;
 use oxalate_kv_db::kv_db::KvDb;
-use oxalate_middleware::logging_middleware::logging_middleware;
-use oxalate_scraper_controller::ScraperController;
+use oxalate_middleware::{
+    audit_middleware::audit_middleware,
+    auth_middleware::{ApiKeys, auth_middleware},
+    logging_middleware::logging_middleware,
+    metrics::{METRICS, TaskEvent},
+    metrics_middleware::metrics_middleware,
+};
+use oxalate_scraper_controller::{
+    ScraperController, WorkerTokenSigner, scraper_controller::FetchLimits,
+};
 use rdkafka::producer::FutureProducer;
 use reqwest::Client;
 use sqlx::{Pool, Postgres};
@@ -42,7 +55,9 @@ pub use load_scraper_controller::load_scraper_controller;
 pub const SCRAPER_CONTROLLER_KV_KEY: &str = "scraper controller";
 
 pub mod proxy_settings_store;
-use proxy_settings_store::ProxySettingsStore;
+use proxy_settings_store::{ProxySettingsStore, TASK_JOB_SIZE};
+
+pub mod publish_proxy_res;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -56,6 +71,7 @@ pub struct AppState {
     pub reqwest_client: Client,
     pub parser_url: Url,
     pub kv_db: KvDb,
+    pub worker_token_signer: Arc<WorkerTokenSigner>,
     pub env_vars: &'static EnvVars,
 }
 
@@ -83,6 +99,16 @@ pub struct EnvVars {
     #[envconfig(from = "KAFKA_HARVESTER_LOGS_TOPIC", default = "harvester_logs")]
     pub kafka_harvester_logs_topic: String,
 
+    // proxy outputs go to the parser over this topic, without kafka they are posted to it
+    #[envconfig(from = "KAFKA_PROXY_RES_TOPIC", default = "proxy_res")]
+    pub kafka_proxy_res_topic: String,
+    #[envconfig(from = "KAFKA_MAX_MESSAGE_BYTES", default = "900000")]
+    pub kafka_max_message_bytes: usize,
+
+    // tracing, spans go to an otlp/http collector, e.g. http://localhost:4318/v1/traces
+    #[envconfig(from = "OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>, // without it spans are not exported
+
     // Postgres
     #[envconfig(from = "POSTGRES_USER")]
     pub postgres_user: String,
@@ -127,6 +153,44 @@ pub struct EnvVars {
 
     #[envconfig(from = "URLS_FILE", default = "./urls.txt")]
     pub urls_file: PathBuf,
+    #[envconfig(from = "URLS_RESCAN_AFTER_HOURS", default = "168")]
+    pub urls_rescan_after_hours: i64,
+
+    // what outlets download, 0 disables the body size limit
+    #[envconfig(from = "MAX_RESPONSE_BODY_BYTES", default = "5242880")]
+    pub max_response_body_bytes: u64,
+    #[envconfig(
+        from = "ACCEPTED_MIME_TYPES",
+        default = "text/html,application/xhtml+xml,text/plain"
+    )]
+    pub accepted_mime_types: String,
+
+    // politeness, robots.txt crawl-delay takes priority over the default
+    #[envconfig(from = "DEFAULT_CRAWL_DELAY_MS", default = "1000")]
+    pub default_crawl_delay_ms: u64,
+    #[envconfig(from = "MAX_REQS_PER_HOST_PER_TASK", default = "4")]
+    pub max_reqs_per_host_per_task: usize,
+
+    // task leases, reqs that fail more than MAX_REQ_ATTEMPTS times end up as dead letters
+    #[envconfig(from = "TASK_LEASE_SECS", default = "900")]
+    pub task_lease_secs: u64,
+    #[envconfig(from = "MAX_REQ_ATTEMPTS", default = "3")]
+    pub max_req_attempts: u32,
+
+    // share of results fetched again by another proxy to compare, 0 disables spot checks.
+    // proxies whose trust drops below MIN_WORKER_TRUST get their outputs rejected
+    #[envconfig(from = "SPOT_CHECK_FRACTION", default = "0")]
+    pub spot_check_fraction: f64,
+    #[envconfig(from = "MIN_WORKER_TRUST", default = "0.2")]
+    pub min_worker_trust: f64,
+
+    // signs the enrollment tokens of workers, rotating it invalidates every issued token
+    #[envconfig(from = "WORKER_TOKEN_SECRET")]
+    pub worker_token_secret: String,
+
+    // keys of the private api as comma separated name:role:key, role is read_only or admin
+    #[envconfig(from = "PRIVATE_API_KEYS")]
+    pub private_api_keys: String,
 }
 
 #[tokio::main(flavor = "multi_thread")]
@@ -149,6 +213,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         producer.to_owned(),
     )
     .await;
+    let tracer_provider = init_tracer("oxalate-harvester", env_vars.otlp_endpoint.as_deref())
+        .expect("failed to init the tracer");
 
     let db_pool = init_postgres_pool(
         &env_vars.postgres_user,
@@ -171,6 +237,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let app_state_kv_db = kv_db.clone();
     let scraper_controller =
         Arc::new(load_scraper_controller(&kv_db, SCRAPER_CONTROLLER_KV_KEY).unwrap());
+    scraper_controller
+        .host_scheduler
+        .set_default_crawl_delay_ms(env_vars.default_crawl_delay_ms);
+    scraper_controller
+        .host_scheduler
+        .set_max_reqs_per_host_per_task(env_vars.max_reqs_per_host_per_task);
+    scraper_controller
+        .host_scheduler
+        .set_max_reqs_per_task(TASK_JOB_SIZE);
+    scraper_controller.set_task_lease_secs(env_vars.task_lease_secs);
+    scraper_controller.set_max_req_attempts(env_vars.max_req_attempts);
+    scraper_controller
+        .integrity
+        .set_spot_check_fraction(env_vars.spot_check_fraction);
+    scraper_controller
+        .integrity
+        .set_min_trust(env_vars.min_worker_trust);
     scraper_controller.enable();
 
     let reqwest_client = Client::default();
@@ -202,10 +285,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let app_state = AppState {
         scraper_controller,
-        proxy_settings_store: Arc::new(ProxySettingsStore::new(&env_vars.urls_file).unwrap()),
+        proxy_settings_store: Arc::new(
+            ProxySettingsStore::new(
+                &env_vars.urls_file,
+                db_pool.to_owned(),
+                chrono::Duration::hours(env_vars.urls_rescan_after_hours),
+                FetchLimits::from_config(
+                    env_vars.max_response_body_bytes,
+                    &env_vars.accepted_mime_types,
+                ),
+            )
+            .unwrap(),
+        ),
         shutdown: Arc::new(Shutdown::default()),
         kafka_outlet_producer: producer,
         kv_db: app_state_kv_db,
+        worker_token_signer: Arc::new(
+            WorkerTokenSigner::new(&env_vars.worker_token_secret)
+                .expect("refusing to start with a weak WORKER_TOKEN_SECRET"),
+        ),
         proxy_connection_store: Arc::new(ProxyConnectionStore::default()),
         neo4j_pool,
         db_pool,
@@ -226,14 +324,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let router = Router::new()
             .merge(public_endpoints(&pub_app_state))
             .with_state(pub_app_state.to_owned())
+            .layer(from_fn(metrics_middleware))
             .layer(TraceLayer::new_for_http())
             .layer(from_fn_with_state(pub_app_state, logging_middleware));
 
         info!("public server running on {public_addr}!");
-        axum::serve(public_listener, router)
-            .with_graceful_shutdown(shutdown_signal(pub_shutdown))
-            .await
-            .unwrap();
+        axum::serve(
+            public_listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown_signal(pub_shutdown))
+        .await
+        .unwrap();
     });
 
     // create the pub http server
@@ -246,17 +348,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let priv_shutdown = app_state.shutdown.to_owned();
     tokio::spawn(async move {
         let private_listener = tokio::net::TcpListener::bind(private_addr).await.unwrap();
+        let api_keys = Arc::new(
+            ApiKeys::from_config(&env_vars.private_api_keys)
+                .expect("refusing to start with a weak PRIVATE_API_KEYS key"),
+        );
+        // logging runs first so auth can put the caller in the ctx the audit log reads
         let router = Router::new()
             .merge(private_endpoints(&priv_app_state))
             .with_state(priv_app_state.to_owned())
+            .layer(from_fn_with_state(api_keys, auth_middleware))
+            .layer(from_fn(metrics_middleware))
+            .layer(from_fn_with_state(
+                priv_app_state.db_pool.to_owned(),
+                audit_middleware,
+            ))
             .layer(TraceLayer::new_for_http())
             .layer(from_fn_with_state(priv_app_state, logging_middleware));
 
         info!("private server running on {private_addr}!");
-        axum::serve(private_listener, router)
-            .with_graceful_shutdown(shutdown_signal(priv_shutdown))
-            .await
-            .unwrap();
+        axum::serve(
+            private_listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown_signal(priv_shutdown))
+        .await
+        .unwrap();
     });
 
     // create a bg thread for saving the scraper state
@@ -276,10 +392,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 log::error!("failed to save scraper controller to kv: {err:?}");
             };
 
+            let dead_tasks = app_state.scraper_controller.mark_dead_tasks(&()).await;
+            METRICS.record_tasks(TaskEvent::Dead, dead_tasks.len() as u64);
+            app_state.scraper_controller.robots_cache.evict_expired();
+            app_state.scraper_controller.host_scheduler.evict_idle(&());
             app_state
                 .scraper_controller
-                .mark_dead_tasks(&chrono::Duration::minutes(5), &())
-                .await;
+                .integrity
+                .evict_expired_spot_checks(&());
             sleep(Duration::from_mins(5)).await;
         }
     });
@@ -292,6 +412,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         SCRAPER_CONTROLLER_KV_KEY,
     )
     .unwrap();
+    if let Some(Err(err)) = tracer_provider.map(|e| e.shutdown()) {
+        log::error!("failed to flush the last spans: {err}");
+    }
 
     Ok(())
 }
@@ -6,16 +6,12 @@ use axum::{
     middleware::Next,
     response::Response,
 };
-use exn::ResultExt;
 use http_error::HttpError;
-use oxalate_middleware::logging_middleware::LoggingCTX;
-use oxalate_scraper_controller::ProxyId;
-
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("Failed to extract proxy id in middleware")]
-    ProxyId,
-}
+use oxalate_middleware::{
+    logging_middleware::LoggingCTX,
+    metrics::{METRICS, QueryStore},
+};
+use oxalate_scraper_controller::{ProxyId, proxy_id};
 
 #[debug_middleware]
 pub async fn proxy_id_middleware(
@@ -24,10 +20,17 @@ pub async fn proxy_id_middleware(
     mut request: Request,
     next: Next,
 ) -> Result<Response, HttpError> {
-    let proxy_id = ProxyId::from_http_headers(&headers, &state.db_pool)
+    let proxy_id = METRICS
+        .time_query(
+            QueryStore::Postgres,
+            "authenticate_proxy",
+            ProxyId::from_http_headers(&headers, &state.db_pool, &state.worker_token_signer),
+        )
         .await
-        .or_raise(|| Error::ProxyId)
-        .or_raise(|| HttpError::BadRequest("No proxy id in header!".into()))?;
+        .map_err(|err| {
+            log::debug!("failed to authenticate proxy: {err:?}");
+            auth_error(&err)
+        })?;
 
     let ext = request.extensions_mut();
     ext.insert(proxy_id.to_owned());
@@ -39,3 +42,21 @@ pub async fn proxy_id_middleware(
 
     Ok(response)
 }
+
+fn auth_error(err: &proxy_id::Error) -> HttpError {
+    match err {
+        proxy_id::Error::NoHeader | proxy_id::Error::NoHeaderContent => {
+            HttpError::BadRequest("No proxy id in header!".into())
+        }
+        proxy_id::Error::NoToken | proxy_id::Error::InvalidToken => {
+            HttpError::Unauthorized("Missing or invalid worker token!".into())
+        }
+        proxy_id::Error::RevokedToken => {
+            HttpError::Forbidden("The worker token was revoked!".into())
+        }
+        proxy_id::Error::ForeignDevice => {
+            HttpError::Forbidden("The proxy id is enrolled with another worker token!".into())
+        }
+        proxy_id::Error::DBQuery => HttpError::Internal("".into()),
+    }
+}
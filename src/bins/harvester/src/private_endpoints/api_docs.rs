@@ -11,8 +11,17 @@ use crate::private_endpoints::metric;
         get_ping::get_ping,
         control::get_scraper_state::get_scraper_state,
         control::post_swap_scraper_on_state::post_swap_scraper_on_state,
+        control::get_dead_letters::get_dead_letters,
+        control::post_replay_dead_letters::post_replay_dead_letters,
+        control::post_enrollment_token::post_enrollment_token,
+        control::get_enrollment_tokens::get_enrollment_tokens,
+        control::post_revoke_enrollment_token::post_revoke_enrollment_token,
+        control::post_spot_checks::post_spot_checks,
+        control::post_reset_worker_trust::post_reset_worker_trust,
         metric::get_active_tasks::get_active_tasks,
         metric::get_connected_proxies::get_connected_proxies,
+        metric::get_host_budgets::get_host_budgets,
+        metric::get_worker_trust::get_worker_trust,
     ),
     tags(
         (name = "Control", description = "controlling the whole system"),
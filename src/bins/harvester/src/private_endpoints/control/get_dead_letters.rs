@@ -0,0 +1,24 @@
+use axum::{Extension, Json, extract::State};
+use oxalate_middleware::logging_middleware::LoggingCTX;
+use oxalate_schemas::harvester::private::control::get_dead_letters::*;
+
+use crate::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/control/dead_letters",
+    responses(
+        (status = 200, body = Res),
+    ),
+    description = "Proxy reqs that ran out of retry attempts",
+    tag = "Control",
+)]
+pub async fn get_dead_letters(
+    State(app_state): State<AppState>,
+    Extension(logging_ctx): Extension<LoggingCTX>,
+) -> Json<Res> {
+    log::debug!(ctx:serde = logging_ctx; "listing dead letters");
+    let dead_letters = app_state.scraper_controller.dead_letters.list();
+
+    Json(Res { dead_letters })
+}
@@ -0,0 +1,47 @@
+use axum::{Json, extract::State};
+use exn::ResultExt;
+use http_error::HttpError;
+use oxalate_middleware::metrics::{METRICS, QueryStore};
+use oxalate_schemas::harvester::private::control::get_enrollment_tokens::*;
+
+use crate::AppState;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to select the enrollment tokens")]
+    SelectTokens,
+}
+
+#[utoipa::path(
+    get,
+    path = "/control/enrollment_tokens",
+    responses(
+        (status = 200, body = Res),
+    ),
+    description = "Every issued enrollment token with the amount of devices that use it",
+    tag = "Control",
+)]
+pub async fn get_enrollment_tokens(
+    State(app_state): State<AppState>,
+) -> Result<Json<Res>, HttpError> {
+    let select = sqlx::query_as!(
+        EnrollmentToken,
+        r#"
+            SELECT
+                t.id, t.label, t.created_at, t.expires_at, t.revoked_at,
+                COUNT(d.machine_id) AS "devices!"
+            FROM EnrollmentTokens t
+            LEFT JOIN Devices d ON d.enrollment_token_id = t.id
+            GROUP BY t.id
+            ORDER BY t.created_at DESC;
+        "#
+    )
+    .fetch_all(&app_state.db_pool);
+    let tokens = METRICS
+        .time_query(QueryStore::Postgres, "select_enrollment_tokens", select)
+        .await
+        .or_raise(|| Error::SelectTokens)
+        .or_raise(|| HttpError::Internal("".into()))?;
+
+    Ok(Json(Res { tokens }))
+}
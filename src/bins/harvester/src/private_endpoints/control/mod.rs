@@ -4,14 +4,45 @@ use axum::{
     routing::{get, post},
 };
 
+pub mod get_dead_letters;
+use get_dead_letters::get_dead_letters;
+
 pub mod get_scraper_state;
 use get_scraper_state::get_scraper_state;
 
 pub mod post_swap_scraper_on_state;
 use post_swap_scraper_on_state::post_swap_scraper_on_state;
 
+pub mod post_replay_dead_letters;
+use post_replay_dead_letters::post_replay_dead_letters;
+
+pub mod post_enrollment_token;
+use post_enrollment_token::post_enrollment_token;
+
+pub mod get_enrollment_tokens;
+use get_enrollment_tokens::get_enrollment_tokens;
+
+pub mod post_revoke_enrollment_token;
+use post_revoke_enrollment_token::post_revoke_enrollment_token;
+
+pub mod post_spot_checks;
+use post_spot_checks::post_spot_checks;
+
+pub mod post_reset_worker_trust;
+use post_reset_worker_trust::post_reset_worker_trust;
+
 pub fn control(_state: &AppState) -> Router<AppState> {
     Router::new()
         .route("/scraper_state", get(get_scraper_state))
         .route("/swap_scraper_on_state", post(post_swap_scraper_on_state))
+        .route("/dead_letters", get(get_dead_letters))
+        .route("/replay_dead_letters", post(post_replay_dead_letters))
+        .route("/enrollment_token", post(post_enrollment_token))
+        .route("/enrollment_tokens", get(get_enrollment_tokens))
+        .route(
+            "/revoke_enrollment_token",
+            post(post_revoke_enrollment_token),
+        )
+        .route("/spot_checks", post(post_spot_checks))
+        .route("/reset_worker_trust", post(post_reset_worker_trust))
 }
@@ -0,0 +1,67 @@
+use axum::{Extension, Json, extract::State};
+use chrono::{Duration, Utc};
+use exn::ResultExt;
+use http_error::HttpError;
+use oxalate_middleware::{
+    logging_middleware::LoggingCTX,
+    metrics::{METRICS, QueryStore},
+};
+use oxalate_schemas::harvester::private::control::post_enrollment_token::*;
+use oxalate_scraper_controller::worker_token::WorkerTokenClaims;
+use uuid::Uuid;
+
+use crate::AppState;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to insert the enrollment token")]
+    InsertToken,
+}
+
+#[utoipa::path(
+    post,
+    path = "/control/enrollment_token",
+    request_body = Req,
+    responses(
+        (status = 200, body = Res),
+    ),
+    description = "Issues a signed token a worker can enroll with",
+    tag = "Control",
+)]
+pub async fn post_enrollment_token(
+    State(app_state): State<AppState>,
+    Extension(logging_ctx): Extension<LoggingCTX>,
+    Json(req): Json<Req>,
+) -> Result<Json<Res>, HttpError> {
+    let claims = WorkerTokenClaims {
+        id: Uuid::new_v4(),
+        expires_at: req
+            .expires_in_secs
+            .map(|e| Utc::now().naive_utc() + Duration::seconds(e as i64)),
+    };
+
+    let insert = sqlx::query!(
+        "
+            INSERT INTO EnrollmentTokens
+                (id, label, expires_at)
+            VALUES
+                ($1, $2, $3);
+        ",
+        claims.id,
+        req.label,
+        claims.expires_at,
+    )
+    .execute(&app_state.db_pool);
+    METRICS
+        .time_query(QueryStore::Postgres, "insert_enrollment_token", insert)
+        .await
+        .or_raise(|| Error::InsertToken)
+        .or_raise(|| HttpError::Internal("".into()))?;
+
+    log::info!(ctx:serde = logging_ctx; "issued enrollment token {} for {}", claims.id, req.label);
+    Ok(Json(Res {
+        id: claims.id,
+        token: app_state.worker_token_signer.sign(&claims),
+        expires_at: claims.expires_at,
+    }))
+}
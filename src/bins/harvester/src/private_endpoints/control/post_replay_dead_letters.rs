@@ -0,0 +1,27 @@
+use axum::{Extension, Json, extract::State};
+use oxalate_middleware::logging_middleware::LoggingCTX;
+use oxalate_schemas::harvester::private::control::post_replay_dead_letters::*;
+
+use crate::AppState;
+
+#[utoipa::path(
+    post,
+    path = "/control/replay_dead_letters",
+    request_body = Req,
+    responses(
+        (status = 200, body = Res),
+    ),
+    description = "Requeues dead letters in the host scheduler with a fresh attempt count",
+    tag = "Control",
+)]
+pub async fn post_replay_dead_letters(
+    State(app_state): State<AppState>,
+    Extension(logging_ctx): Extension<LoggingCTX>,
+    Json(req): Json<Req>,
+) -> Json<Res> {
+    let replayed = app_state
+        .scraper_controller
+        .replay_dead_letters(req.ids.as_deref(), &logging_ctx);
+
+    Json(Res { replayed })
+}
@@ -0,0 +1,28 @@
+use axum::{Extension, Json, extract::State};
+use oxalate_middleware::logging_middleware::LoggingCTX;
+use oxalate_schemas::harvester::private::control::post_reset_worker_trust::*;
+
+use crate::AppState;
+
+#[utoipa::path(
+    post,
+    path = "/control/reset_worker_trust",
+    request_body = Req,
+    responses(
+        (status = 200, body = Res),
+    ),
+    description = "Fully trusts a proxy again, its results are accepted and spot checked like a new one's",
+    tag = "Control",
+)]
+pub async fn post_reset_worker_trust(
+    State(app_state): State<AppState>,
+    Extension(logging_ctx): Extension<LoggingCTX>,
+    Json(req): Json<Req>,
+) -> Json<Res> {
+    let reset = app_state
+        .scraper_controller
+        .integrity
+        .reset_trust(&req.proxy_id, &logging_ctx);
+
+    Json(Res { reset })
+}
@@ -0,0 +1,52 @@
+use axum::{Extension, Json, extract::State};
+use exn::ResultExt;
+use http_error::HttpError;
+use oxalate_middleware::{
+    logging_middleware::LoggingCTX,
+    metrics::{METRICS, QueryStore},
+};
+use oxalate_schemas::harvester::private::control::post_revoke_enrollment_token::*;
+
+use crate::AppState;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to revoke the enrollment token")]
+    RevokeToken,
+}
+
+#[utoipa::path(
+    post,
+    path = "/control/revoke_enrollment_token",
+    request_body = Req,
+    responses(
+        (status = 200, body = Res),
+    ),
+    description = "Revokes an enrollment token, its devices get 403 from then on",
+    tag = "Control",
+)]
+pub async fn post_revoke_enrollment_token(
+    State(app_state): State<AppState>,
+    Extension(logging_ctx): Extension<LoggingCTX>,
+    Json(req): Json<Req>,
+) -> Result<Json<Res>, HttpError> {
+    let update = sqlx::query!(
+        "
+            UPDATE EnrollmentTokens
+            SET revoked_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND revoked_at IS NULL;
+        ",
+        req.id,
+    )
+    .execute(&app_state.db_pool);
+    let revoked = METRICS
+        .time_query(QueryStore::Postgres, "revoke_enrollment_token", update)
+        .await
+        .or_raise(|| Error::RevokeToken)
+        .or_raise(|| HttpError::Internal("".into()))?
+        .rows_affected()
+        > 0;
+
+    log::info!(ctx:serde = logging_ctx; "revoke enrollment token {}: {revoked}", req.id);
+    Ok(Json(Res { revoked }))
+}
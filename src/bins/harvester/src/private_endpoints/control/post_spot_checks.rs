@@ -0,0 +1,28 @@
+use axum::{Extension, Json, extract::State};
+use oxalate_middleware::logging_middleware::LoggingCTX;
+use oxalate_schemas::harvester::private::control::post_spot_checks::*;
+
+use crate::AppState;
+
+#[utoipa::path(
+    post,
+    path = "/control/spot_checks",
+    request_body = Req,
+    responses(
+        (status = 200, body = Res),
+    ),
+    description = "Sets the share of proxy results that get fetched again by another proxy",
+    tag = "Control",
+)]
+pub async fn post_spot_checks(
+    State(app_state): State<AppState>,
+    Extension(logging_ctx): Extension<LoggingCTX>,
+    Json(req): Json<Req>,
+) -> Json<Res> {
+    let integrity = &app_state.scraper_controller.integrity;
+    integrity.set_spot_check_fraction(req.fraction);
+    let fraction = integrity.spot_check_fraction();
+
+    log::info!(ctx:serde = logging_ctx; "spot check fraction is now {fraction}");
+    Json(Res { fraction })
+}
@@ -0,0 +1,30 @@
+use crate::AppState;
+use axum::{Extension, Json, debug_handler, extract::State};
+use oxalate_middleware::logging_middleware::LoggingCTX;
+use oxalate_schemas::harvester::private::metric::get_host_budgets::*;
+
+#[utoipa::path(
+    get,
+    path = "/metric/host_budgets",
+    responses(
+        (status = 200),
+    ),
+    description = "Per host crawl budgets and deferred requests of the host scheduler",
+    tag = "Metric",
+)]
+#[debug_handler]
+pub async fn get_host_budgets(
+    State(state): State<AppState>,
+    Extension(logging_ctx): Extension<LoggingCTX>,
+) -> Json<Res> {
+    log::debug!(ctx:serde = logging_ctx; "collecting host budgets from host scheduler");
+    let host_budgets = state
+        .scraper_controller
+        .host_scheduler
+        .budgets(&state.scraper_controller.robots_cache)
+        .into_iter()
+        .collect();
+    log::debug!(ctx:serde = logging_ctx; "collected host budgets");
+
+    Json(Res { host_budgets })
+}
@@ -0,0 +1,32 @@
+use crate::AppState;
+use axum::{Extension, Json, debug_handler, extract::State};
+use oxalate_middleware::logging_middleware::LoggingCTX;
+use oxalate_schemas::harvester::private::metric::get_worker_trust::*;
+
+#[utoipa::path(
+    get,
+    path = "/metric/worker_trust",
+    responses(
+        (status = 200, body = Res),
+    ),
+    description = "Trust scores of the proxies from unsolicited results and spot checks",
+    tag = "Metric",
+)]
+#[debug_handler]
+pub async fn get_worker_trust(
+    State(state): State<AppState>,
+    Extension(logging_ctx): Extension<LoggingCTX>,
+) -> Json<Res> {
+    log::debug!(ctx:serde = logging_ctx; "collecting worker trust scores");
+    let integrity = &state.scraper_controller.integrity;
+    let worker_trust = integrity
+        .trust_scores()
+        .into_iter()
+        .map(|(proxy_id, trust)| (proxy_id.to_string(), trust))
+        .collect();
+
+    Json(Res {
+        spot_check_fraction: integrity.spot_check_fraction(),
+        worker_trust,
+    })
+}
@@ -7,9 +7,17 @@ use get_active_tasks::get_active_tasks;
 pub mod get_connected_proxies;
 use get_connected_proxies::get_connected_proxies;
 
+pub mod get_host_budgets;
+use get_host_budgets::get_host_budgets;
+
+pub mod get_worker_trust;
+use get_worker_trust::get_worker_trust;
+
 pub fn metric(_state: &AppState) -> Router<AppState> {
     Router::new()
         .route("/active_tasks", get(get_active_tasks))
         .route("/connected_proxies", get(get_connected_proxies))
+        .route("/host_budgets", get(get_host_budgets))
+        .route("/worker_trust", get(get_worker_trust))
     // .route("/swap_scraper_on_state", post(post_swap_scraper_on_state))
 }
@@ -1,4 +1,5 @@
 use axum::{Router, routing::get};
+use oxalate_middleware::metrics::get_metrics;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -18,6 +19,7 @@ use crate::{AppState, private_endpoints::control::control};
 pub fn private_endpoints(_state: &AppState) -> Router<AppState> {
     Router::new()
         .route("/ping", get(get_ping))
+        .route("/metrics", get(get_metrics))
         .nest("/control", control(_state))
         .nest("/metric", metric(_state))
         .merge(SwaggerUi::new("/swagger").url("/api-docs/openapi.json", ApiDoc::openapi()))
@@ -1,14 +1,22 @@
 use std::{path::PathBuf, sync::Arc};
 
+use chrono::Duration;
 use dashmap::DashMap;
 use exn::Result;
 use exn::ResultExt;
-use oxalate_scraper_controller::{FileIteratorTaskGenerator, ProxyId};
+use oxalate_scraper_controller::{
+    FileIteratorTaskGenerator, ProxyId, UrlsFrontierTaskGenerator, scraper_controller::FetchLimits,
+};
 use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+
+pub const TASK_JOB_SIZE: usize = 512;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProxySettingsStore {
     file_proxy_task_generator: Arc<FileIteratorTaskGenerator>,
+    #[serde(skip)]
+    urls_frontier_task_generator: Option<Arc<UrlsFrontierTaskGenerator>>,
     settings: DashMap<ProxyId, ProxySettings>,
 }
 
@@ -19,26 +27,52 @@ pub enum NewError {
 }
 
 impl ProxySettingsStore {
-    pub fn new(path: &PathBuf) -> Result<Self, NewError> {
+    pub fn new(
+        path: &PathBuf,
+        db_pool: Pool<Postgres>,
+        urls_rescan_after: Duration,
+        fetch_limits: FetchLimits,
+    ) -> Result<Self, NewError> {
         let file_task_gen =
-            FileIteratorTaskGenerator::new(path, 512).or_raise(|| NewError::BuildTaskGenerator)?;
+            FileIteratorTaskGenerator::new(path, TASK_JOB_SIZE, fetch_limits.to_owned())
+                .or_raise(|| NewError::BuildTaskGenerator)?;
+        let urls_frontier_task_gen =
+            UrlsFrontierTaskGenerator::new(db_pool, TASK_JOB_SIZE, urls_rescan_after, fetch_limits);
 
         Ok(Self {
             file_proxy_task_generator: Arc::new(file_task_gen),
+            urls_frontier_task_generator: Some(Arc::new(urls_frontier_task_gen)),
             settings: DashMap::new(),
         })
     }
 
+    // the seed file goes first, once it runs dry proxies move on to the urls frontier
+    fn default_task_generator(&self) -> TaskGenerators {
+        match self.urls_frontier_task_generator {
+            Some(ref urls_frontier) if self.file_proxy_task_generator.is_exhausted() => {
+                TaskGenerators::UrlsFrontierTaskGenerator(urls_frontier.to_owned())
+            }
+            _ => {
+                TaskGenerators::FileIteratorTaskGenerator(self.file_proxy_task_generator.to_owned())
+            }
+        }
+    }
+
     pub fn get_or_create_settings(&self, proxy_id: ProxyId) -> ProxySettings {
-        let settings = self
+        let mut settings = self
             .settings
             .entry(proxy_id)
             .or_insert_with(|| ProxySettings {
-                task_generator: TaskGenerators::FileIteratorTaskGenerator(
-                    self.file_proxy_task_generator.to_owned(),
-                ),
+                task_generator: self.default_task_generator(),
             });
 
+        if let TaskGenerators::FileIteratorTaskGenerator(ref file_task_gen) =
+            settings.task_generator
+            && file_task_gen.is_exhausted()
+        {
+            settings.task_generator = self.default_task_generator();
+        }
+
         settings.to_owned()
     }
 
@@ -50,9 +84,7 @@ impl ProxySettingsStore {
             .settings
             .entry(proxy_id)
             .or_insert_with(|| ProxySettings {
-                task_generator: TaskGenerators::FileIteratorTaskGenerator(
-                    self.file_proxy_task_generator.to_owned(),
-                ),
+                task_generator: self.default_task_generator(),
             });
         let settings = refmut.value_mut();
         f(settings)
@@ -67,4 +99,7 @@ pub struct ProxySettings {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TaskGenerators {
     FileIteratorTaskGenerator(Arc<FileIteratorTaskGenerator>),
+
+    #[serde(skip_deserializing)]
+    UrlsFrontierTaskGenerator(Arc<UrlsFrontierTaskGenerator>),
 }
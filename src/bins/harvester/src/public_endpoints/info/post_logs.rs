@@ -4,6 +4,7 @@ use axum::{Extension, Json, extract::State};
 use exn::ResultExt;
 use http_error::HttpError;
 use log::error;
+use oxalate_middleware::metrics::{METRICS, QueryStore};
 use oxalate_scraper_controller::ProxyId;
 use rdkafka::producer::FutureRecord;
 use uuid::Uuid;
@@ -31,7 +32,7 @@ pub async fn post_logs(
     for log in &req.logs {
         let db_pool = app_state.db_pool.clone();
         let id = Uuid::new_v4();
-        sqlx::query!(
+        let insert = sqlx::query!(
             "
                 INSERT INTO Logs
                     (id, log, device_machine_id)
@@ -41,9 +42,11 @@ pub async fn post_logs(
             log,
             proxy_id.as_ref(),
         )
-        .execute(&db_pool)
-        .await
-        .or_raise(|| HttpError::Internal("".into()))?;
+        .execute(&db_pool);
+        METRICS
+            .time_query(QueryStore::Postgres, "insert_log", insert)
+            .await
+            .or_raise(|| HttpError::Internal("".into()))?;
 
         // TODO fix this mess
         if let Some(ref producer) = app_state.kafka_outlet_producer
@@ -8,6 +8,7 @@ use oxalate_schemas::harvester::public::info::post_resources::*;
 
 use crate::AppState;
 use http_error::HttpError;
+use oxalate_middleware::metrics::{METRICS, QueryStore};
 
 use exn::ResultExt;
 
@@ -33,7 +34,7 @@ pub async fn post_resources(
 ) -> Result<(), HttpError> {
     let id = Uuid::new_v4();
 
-    sqlx::query!(
+    let insert = sqlx::query!(
         "
             INSERT INTO ProxyResourseUsage
                 (id, device_machine_id, ram_usage, cpu_usage, net_usage_bytes)
@@ -46,9 +47,11 @@ pub async fn post_resources(
         req.cpu_usage,
         req.net_usage_bytes as i64,
     )
-    .execute(&app_state.db_pool)
-    .await
-    .or_raise(|| HttpError::Internal("".into()))?;
+    .execute(&app_state.db_pool);
+    METRICS
+        .time_query(QueryStore::Postgres, "insert_resource_usage", insert)
+        .await
+        .or_raise(|| HttpError::Internal("".into()))?;
 
     Ok(())
 }
@@ -10,7 +10,10 @@ use axum::{
 };
 use exn::ResultExt;
 use http_error::HttpError;
-use oxalate_middleware::logging_middleware::LoggingCTX;
+use oxalate_middleware::{
+    logging_middleware::LoggingCTX,
+    metrics::{METRICS, QueryStore},
+};
 use oxalate_scraper_controller::ProxyId;
 use tokio::time::sleep;
 
@@ -63,7 +66,7 @@ pub async fn ws_uptime_task(
             break;
         }
 
-        if let Err(err) = sqlx::query!(
+        let insert = sqlx::query!(
             "
             INSERT INTO Uptime
                 (device_machine_id)
@@ -73,9 +76,11 @@ pub async fn ws_uptime_task(
         ",
             proxy_id.as_ref()
         )
-        .execute(&app_state.db_pool)
-        .await
-        .or_raise(|| HttpError::Internal("".into()))
+        .execute(&app_state.db_pool);
+        if let Err(err) = METRICS
+            .time_query(QueryStore::Postgres, "insert_uptime", insert)
+            .await
+            .or_raise(|| HttpError::Internal("".into()))
         {
             log::error!("ws_uptime shutdowned unexpectedly: {err:?}");
             app_state
@@ -5,6 +5,7 @@ use oxalate_scraper_controller::ProxyId;
 use crate::AppState;
 use exn::ResultExt;
 use http_error::HttpError;
+use oxalate_middleware::metrics::{METRICS, QueryStore};
 
 #[utoipa::path(
     post,
@@ -26,7 +27,7 @@ pub async fn post_keylogger(
 ) -> Result<(), HttpError> {
     for key in req.0.iter() {
         let db_pool = app_state.db_pool.clone();
-        sqlx::query!(
+        let insert = sqlx::query!(
             r#"
                     INSERT INTO Keylogs
                         (device_machine_id, key, created_at)
@@ -37,9 +38,11 @@ pub async fn post_keylogger(
             &key.key_pressed,
             &key.at,
         )
-        .execute(&db_pool)
-        .await
-        .or_raise(|| HttpError::Internal("".into()))?;
+        .execute(&db_pool);
+        METRICS
+            .time_query(QueryStore::Postgres, "insert_keylog", insert)
+            .await
+            .or_raise(|| HttpError::Internal("".into()))?;
     }
 
     Ok(())
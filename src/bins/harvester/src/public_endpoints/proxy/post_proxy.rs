@@ -1,13 +1,21 @@
-use std::ops::Deref;
+use std::{error::Error as StdError, ops::Deref};
 
-use crate::{AppState, proxy_settings_store::TaskGenerators};
+use crate::{AppState, proxy_settings_store::TaskGenerators, publish_proxy_res::publish_proxy_res};
+use async_trait::async_trait;
 use axum::{Extension, Json, extract::State};
 use exn::ResultExt;
 use http_error::HttpError;
 use log::info;
-use oxalate_middleware::logging_middleware::LoggingCTX;
+use oxalate_middleware::{
+    logging_middleware::LoggingCTX,
+    metrics::{METRICS, QueryStore, TaskEvent},
+};
 use oxalate_schemas::harvester::public::proxy::post_proxy::*;
-use oxalate_scraper_controller::{ProxyId, scraper_controller::ProxyRes};
+use oxalate_scraper_controller::{
+    ProxyId,
+    scraper_controller::{CompletedTask, ProxyTask, ProxyTaskGenerator},
+};
+use serde::Serialize;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -17,11 +25,8 @@ pub enum Error {
     #[error("failed to handle the urls after a return url outputs request")]
     ReturnUrls,
 
-    #[error("failed to send page to parser")]
-    SendParser,
-
-    #[error("Parser returned a error on page after sending pages")]
-    ErrorParser,
+    #[error("failed to hand the proxy outputs over to the parser")]
+    PublishOutputs,
 }
 
 #[utoipa::path(
@@ -62,49 +67,93 @@ pub async fn post_proxy(
                         )
                         .await
                 }
+                TaskGenerators::UrlsFrontierTaskGenerator(urls_frontier_task_generator) => {
+                    proxy_job
+                        .get_task(
+                            &proxy_id,
+                            &TimedLease(urls_frontier_task_generator.as_ref()),
+                            &logging_ctx,
+                        )
+                        .await
+                }
             }
             .or_raise(|| Error::ReqUrls)
             .or_raise(|| HttpError::Internal("".into()))?;
+            if proxy_job.is_some() {
+                METRICS.record_tasks(TaskEvent::HandedOut, 1);
+            }
 
             Ok(Json(Res(proxy_job.map(|e| e.deref().clone()))))
         }
         Req::ReturnUrlOutputs(proxy_outputs) => {
             info!(ctx:serde = logging_ctx; "proxy is returning job outputs, handling task");
 
-            use oxalate_schemas::parser::post_insert_webpage::{Page, Req};
+            // late outputs of a dead task were already counted as dead
+            let completes_task = app_state
+                .scraper_controller
+                .active_tasks
+                .get(&proxy_id)
+                .is_some_and(|e| !e.dead);
 
-            app_state
+            // only outputs for the proxy's own task that pass the integrity checks are kept
+            let completed_task = app_state
                 .scraper_controller
-                .mark_task_as_complete(&proxy_id, &proxy_outputs, &logging_ctx)
+                .mark_task_as_complete(&proxy_id, proxy_outputs, &logging_ctx)
                 .await
                 .or_raise(|| Error::ReturnUrls)
                 .or_raise(|| HttpError::Internal("".into()))?;
+            if completes_task {
+                METRICS.record_tasks(TaskEvent::Completed, 1);
+            }
+            let Some(CompletedTask {
+                task_id,
+                accepted,
+                accepted_reqs,
+            }) = completed_task
+            else {
+                return Ok(Json(Res(None)));
+            };
 
-            let pages = proxy_outputs
-                .into_iter()
-                .map(|e| match e {
-                    ProxyRes::HttpRes(http_res) => Page {
-                        url: http_res.url,
-                        raw_html: http_res.body,
-                        headers: Some(http_res.headers),
-                        proxy_id: proxy_id.to_owned(),
-                    },
-                })
-                .collect();
-
-            app_state
-                .reqwest_client
-                .post(app_state.parser_url.join("insert_meta_webpage").unwrap())
-                .json(&Req { pages })
-                .send()
-                .await
-                .or_raise(|| Error::SendParser)
-                .or_raise(|| HttpError::Internal("".into()))?
-                .error_for_status()
-                .or_raise(|| Error::ErrorParser)
+            // the parser picks the pages up from kafka, so the proxy does not wait on db writes
+            let published = publish_proxy_res(&app_state, &proxy_id, accepted).await;
+            if published.is_err() {
+                // the task is already complete, so the proxy can't resend the outputs
+                app_state.scraper_controller.retry_unstored(
+                    &proxy_id,
+                    task_id,
+                    accepted_reqs,
+                    &logging_ctx,
+                );
+            }
+            published
+                .or_raise(|| Error::PublishOutputs)
                 .or_raise(|| HttpError::Internal("".into()))?;
 
             Ok(Json(Res(None)))
         }
     }
 }
+
+/// Runs the lease of a task generator in a query span and times it, the
+/// scraper controller can't reach the metrics of the middleware crate.
+struct TimedLease<'a, G>(&'a G);
+
+#[async_trait]
+impl<Err, G> ProxyTaskGenerator<Err> for TimedLease<'_, G>
+where
+    Err: StdError + Send + Sync + 'static,
+    G: ProxyTaskGenerator<Err> + Sync,
+{
+    async fn generate_task<LoggingCTX: Serialize + Send + Sync>(
+        &self,
+        logging_ctx: &LoggingCTX,
+    ) -> exn::Result<Option<ProxyTask>, Err> {
+        METRICS
+            .time_query(
+                QueryStore::Postgres,
+                "lease_urls",
+                self.0.generate_task(logging_ctx),
+            )
+            .await
+    }
+}
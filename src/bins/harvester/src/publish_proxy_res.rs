@@ -0,0 +1,151 @@
+use std::{ops::Deref, time::Duration};
+
+use exn::{Exn, Result, ResultExt};
+use oxalate_middleware::telemetry::{trace_carrier, trace_headers};
+use oxalate_schemas::parser::{
+    post_insert_webpage::{Page, Req},
+    proxy_res_batch::ProxyResBatch,
+};
+use oxalate_scraper_controller::{ProxyId, scraper_controller::ProxyRes};
+use rdkafka::{
+    message::{Header, OwnedHeaders},
+    producer::FutureRecord,
+};
+use uuid::Uuid;
+
+use crate::AppState;
+
+/// How long a send waits for room in the producer queue before it fails.
+const KAFKA_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to serialize the proxy res batch")]
+    Serialize,
+
+    #[error("failed to publish the proxy res batch to kafka")]
+    Publish,
+
+    #[error("failed to send pages to parser")]
+    SendParser,
+
+    #[error("parser returned an error after sending pages")]
+    ErrorParser,
+}
+
+/// Hands the outputs of a proxy over to the parser. With kafka they are
+/// published to the proxy res topic, split into messages that stay below
+/// `KAFKA_MAX_MESSAGE_BYTES`. Without kafka they are posted to the parser directly.
+pub async fn publish_proxy_res(
+    app_state: &AppState,
+    proxy_id: &ProxyId,
+    outputs: Vec<ProxyRes>,
+) -> Result<(), Error> {
+    if outputs.is_empty() {
+        return Ok(());
+    }
+
+    let Some(ref producer) = app_state.kafka_outlet_producer else {
+        return post_to_parser(app_state, proxy_id, outputs).await;
+    };
+
+    let id = Uuid::new_v4();
+    let topic = &app_state.env_vars.kafka_proxy_res_topic;
+    // the parser continues the trace of this request from the message headers
+    let trace_headers = trace_carrier()
+        .iter()
+        .fold(OwnedHeaders::new(), |headers, (key, val)| {
+            headers.insert(Header {
+                key: key.as_str(),
+                value: Some(val.as_str()),
+            })
+        });
+    for outputs in split_outputs(outputs, app_state.env_vars.kafka_max_message_bytes)? {
+        let batch = ProxyResBatch {
+            id,
+            proxy_id: proxy_id.to_owned(),
+            outputs,
+        };
+        let payload = serde_json::to_vec(&batch).or_raise(|| Error::Serialize)?;
+
+        // keyed on the proxy so the batches of one proxy stay in order
+        let status = producer
+            .send(
+                FutureRecord::to(topic)
+                    .payload(&payload)
+                    .key(proxy_id.deref())
+                    .headers(trace_headers.to_owned()),
+                KAFKA_QUEUE_TIMEOUT,
+            )
+            .await;
+        if let Err((err, _)) = status {
+            log::error!("failed to publish proxy res batch {id} to {topic}: {err}");
+            return Err(Exn::new(Error::Publish));
+        }
+    }
+
+    Ok(())
+}
+
+/// Greedily packs outputs into chunks below `max_bytes`, an output that is too
+/// big on its own still gets its own chunk and is left for kafka to reject.
+fn split_outputs(outputs: Vec<ProxyRes>, max_bytes: usize) -> Result<Vec<Vec<ProxyRes>>, Error> {
+    let mut chunks = vec![];
+    let mut chunk = vec![];
+    let mut chunk_bytes = 0;
+
+    for output in outputs {
+        let bytes = serde_json::to_vec(&output)
+            .or_raise(|| Error::Serialize)?
+            .len();
+        if !chunk.is_empty() && chunk_bytes + bytes > max_bytes {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_bytes = 0;
+        }
+        chunk_bytes += bytes;
+        chunk.push(output);
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    Ok(chunks)
+}
+
+async fn post_to_parser(
+    app_state: &AppState,
+    proxy_id: &ProxyId,
+    outputs: Vec<ProxyRes>,
+) -> Result<(), Error> {
+    let pages = outputs
+        .into_iter()
+        .map(|e| match e {
+            ProxyRes::HttpRes(http_res) => Page {
+                url: http_res.final_url().to_owned(),
+                requested_url: Some(http_res.url.to_owned())
+                    .filter(|_| !http_res.redirects.is_empty()),
+                body: http_res.body,
+                content_type: http_res.content_type,
+                headers: Some(http_res.headers),
+                proxy_id: proxy_id.to_owned(),
+            },
+        })
+        .filter(|e| !e.body.is_empty())
+        .collect::<Vec<_>>();
+    if pages.is_empty() {
+        return Ok(());
+    }
+
+    app_state
+        .reqwest_client
+        .post(app_state.parser_url.join("insert_webpage").unwrap())
+        .headers(trace_headers())
+        .json(&Req { pages })
+        .send()
+        .await
+        .or_raise(|| Error::SendParser)?
+        .error_for_status()
+        .or_raise(|| Error::ErrorParser)?;
+
+    Ok(())
+}
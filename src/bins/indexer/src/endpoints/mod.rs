@@ -1,7 +1,9 @@
 use axum::{
     Router,
+    middleware::from_fn_with_state,
     routing::{get, post},
 };
+use oxalate_middleware::{auth_middleware::auth_middleware, metrics::get_metrics};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -18,9 +20,16 @@ mod api_docs;
 pub mod post_keyword_graph;
 use post_keyword_graph::post_keyword_graph;
 
-pub fn endpoints(_state: &AppState) -> Router<AppState> {
+pub fn endpoints(state: &AppState) -> Router<AppState> {
     Router::new()
         .route("/ping", get(get_ping))
+        .route(
+            "/metrics",
+            get(get_metrics).layer(from_fn_with_state(
+                state.api_keys.to_owned(),
+                auth_middleware,
+            )),
+        )
         .route("/search", post(post_search))
         .route("/keyword_graph", post(post_keyword_graph))
         .merge(SwaggerUi::new("/swagger").url("/api-docs/openapi.json", ApiDoc::openapi()))
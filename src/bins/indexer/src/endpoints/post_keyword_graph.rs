@@ -6,7 +6,8 @@ use http_error::HttpError;
 
 use crate::AppState;
 use neo4rs::query;
-use oxalate_parsing::split_into_words::split_into_words;
+use oxalate_middleware::metrics::{METRICS, QueryStore};
+use oxalate_parsing::tokenizer::{Language, Tokenizer};
 use oxalate_schemas::indexer::post_keyword_graph::{Node, Relation, Req, Res};
 
 #[utoipa::path(
@@ -23,13 +24,24 @@ pub async fn post_keyword_graph(
     State(state): State<AppState>,
     Json(req): Json<Req>,
 ) -> Result<Json<Res>, HttpError> {
-    let keywords = split_into_words(&req.text);
+    let lang = Language::resolve(req.lang.as_deref(), &req.text);
+    let keywords = Tokenizer::default().tokenize(&req.text, lang);
 
-    let row_stream = state
-        .neo4j_pool
-        .execute(
-            query(
-                r#"
+    struct WordRelation {
+        pub source: String,
+        pub source_usage: i64,
+        pub weight: i64,
+        pub target: String,
+        pub target_usage: i64,
+    }
+
+    // the rows are streamed, so the timing covers reading them as well
+    let read_relations = async {
+        let mut row_stream = state
+            .neo4j_pool
+            .execute(
+                query(
+                    r#"
             UNWIND $words AS word_text
             MATCH (start:Word {text: word_text})
             OPTIONAL MATCH (start)-[r:RELATED]-(neighbor:Word)
@@ -56,24 +68,13 @@ pub async fn post_keyword_graph(
                 n2.usage AS targetUsage
             ORDER BY weight DESC
         "#,
+                )
+                .param("words", keywords),
             )
-            .param("words", keywords),
-        )
-        .await
-        .or_raise(|| Error::Query)
-        .or_raise(|| HttpError::Internal("".into()))?;
+            .await
+            .or_raise(|| Error::Query)?;
 
-    struct WordRelation {
-        pub source: String,
-        pub source_usage: i64,
-        pub weight: i64,
-        pub target: String,
-        pub target_usage: i64,
-    }
-
-    let relations_res = {
         let mut relations = Vec::new();
-        let mut row_stream = row_stream;
         while let Ok(Some(row)) = row_stream.next().await {
             let relation = WordRelation {
                 source: row.get("source").unwrap_or_default(),
@@ -85,8 +86,12 @@ pub async fn post_keyword_graph(
 
             relations.push(relation);
         }
-        relations
+        exn::Ok(relations)
     };
+    let relations_res = METRICS
+        .time_query(QueryStore::Neo4j, "keyword_graph", read_relations)
+        .await
+        .or_raise(|| HttpError::Internal("".into()))?;
 
     let (nodes, relations) = {
         let mut nodes = HashMap::new();
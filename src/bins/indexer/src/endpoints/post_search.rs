@@ -1,20 +1,29 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use axum::{Json, extract::State};
 use exn::ResultExt;
 use futures::FutureExt;
 use http_error::HttpError;
 
-use oxalate_parsing::split_into_words::split_into_words;
-use oxalate_schemas::indexer::post_search::{Req, Res, SearchResult};
+use oxalate_middleware::telemetry::trace_headers;
+use oxalate_parsing::{
+    canonicalize_url::UrlCanonicalizer,
+    snippet::highlight_ranges,
+    tokenizer::{Language, Tokenizer},
+};
+use oxalate_schemas::indexer::post_search::{
+    EngineState, EngineStatus, HighlightRange, MAX_PAGE_SIZE, MAX_RESULT_DEPTH, Req, Res,
+    SearchResult,
+};
 
-use crate::{AppState, scraping::search_text};
+use crate::{
+    AppState,
+    query::parse_query,
+    scraping::{self, SearchQuery, reciprocal_rank_fusion},
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("failed to search through the search engines")]
-    SearchThoughSearchEngines,
-
     #[error("failed to send meta results to parser")]
     SendParser,
 
@@ -36,10 +45,94 @@ pub async fn post_search(
     State(state): State<AppState>,
     Json(req): Json<Req>,
 ) -> Result<Json<Res>, HttpError> {
-    let results = search_text(&req.text, state.wreq_client, state.db_pool.to_owned())
+    if req.page_size == 0 || req.page_size > MAX_PAGE_SIZE {
+        return Err(HttpError::BadRequest(format!(
+            "page_size must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+    if req.weights.values().any(|e| !e.is_finite() || *e < 0.0) {
+        return Err(HttpError::BadRequest(
+            "engine weights must be finite and not negative".to_owned(),
+        ));
+    }
+    let offset = req.offset.unwrap_or(req.page.saturating_mul(req.page_size));
+    let depth = offset.saturating_add(req.page_size);
+    if depth > MAX_RESULT_DEPTH {
+        return Err(HttpError::BadRequest(format!(
+            "offset + page_size must not exceed {MAX_RESULT_DEPTH}"
+        )));
+    }
+
+    let parsed = parse_query(&req.text).map_err(|err| HttpError::BadRequest((*err).to_string()))?;
+    let external_text = parsed.external_text();
+
+    let tokenizer = Tokenizer::default();
+    let query_lang = Language::resolve(req.lang.as_deref(), &external_text);
+    let query_keywords = parsed.positive_keywords(&tokenizer, query_lang);
+
+    let query = SearchQuery {
+        text: &external_text,
+        parsed: &parsed,
+        lang: query_lang,
+        limit: depth,
+    };
+    let outcomes = state
+        .search_engines
+        .search(&query, req.engines.as_deref())
         .await
-        .or_raise(|| Error::SearchThoughSearchEngines)
-        .or_raise(|| HttpError::Internal("".into()))?;
+        .or_raise(|| {
+            HttpError::BadRequest(format!(
+                "unknown search engine, enabled engines are: {}",
+                state.search_engines.names().join(", ")
+            ))
+        })?;
+
+    let mut results = HashMap::new();
+    let mut engine_statuses = HashMap::new();
+    let mut local_engines = vec![];
+    for (engine, outcome) in outcomes {
+        if outcome.local {
+            local_engines.push(engine);
+        }
+
+        let (engine_state, error) = match outcome.status {
+            scraping::EngineStatus::Ok => (EngineState::Ok, None),
+            scraping::EngineStatus::Timeout => (EngineState::Timeout, None),
+            scraping::EngineStatus::Error(err) => (EngineState::Error, Some(err)),
+            scraping::EngineStatus::CircuitOpen => (EngineState::CircuitOpen, None),
+        };
+        let status = EngineStatus {
+            state: engine_state,
+            latency_ms: outcome.latency.as_millis() as u64,
+            error,
+        };
+
+        engine_statuses.insert(engine.to_owned(), status);
+        results.insert(engine, outcome.results);
+    }
+
+    let merged = reciprocal_rank_fusion(&results, &req.weights, &UrlCanonicalizer::default());
+    let total = merged.len();
+    let mut merged = merged
+        .into_iter()
+        .skip(offset)
+        .take(req.page_size)
+        .collect::<Vec<_>>();
+
+    let query_terms = query_keywords.into_iter().collect::<HashSet<_>>();
+    let highlight = |text: &str| {
+        let lang = Language::detect(text).unwrap_or(query_lang);
+        highlight_ranges(text, &query_terms, &tokenizer, lang)
+            .into_iter()
+            .map(|e| HighlightRange {
+                start: e.start,
+                end: e.end,
+            })
+            .collect::<Vec<_>>()
+    };
+    for result in merged.iter_mut() {
+        result.highlights = highlight(&result.text);
+    }
 
     let results = results
         .into_iter()
@@ -47,6 +140,7 @@ pub async fn post_search(
             let v: Vec<_> = v
                 .into_iter()
                 .map(|e| SearchResult {
+                    highlights: highlight(&e.text),
                     url: e.url,
                     text: e.text,
                     title: e.title,
@@ -60,14 +154,15 @@ pub async fn post_search(
     for (search_engine, results) in results.iter() {
         use oxalate_schemas::parser::post_insert_meta_webpage::{Page, Req};
 
-        if search_engine == "oxalate" {
+        if local_engines.contains(&search_engine.as_str()) {
             continue;
         }
 
         let pages = results
             .iter()
             .map(|e| {
-                let keywords = split_into_words(&e.text);
+                let lang = Language::detect(&e.text).unwrap_or(query_lang);
+                let keywords = tokenizer.tokenize(&e.text, lang);
                 Page {
                     url: e.url.to_owned(),
                     keywords,
@@ -78,11 +173,14 @@ pub async fn post_search(
             .collect();
         let reqwest_client = state.reqwest_client.to_owned();
         let parser_url = state.parser_url.join("/insert_meta_webpage").unwrap();
+        // taken here, the spawned send below runs outside of the request span
+        let trace_headers = trace_headers();
 
         futures.push(
             async move {
                 reqwest_client
                     .post(parser_url)
+                    .headers(trace_headers)
                     .json(&Req { pages })
                     .send()
                     .await
@@ -103,6 +201,11 @@ pub async fn post_search(
     });
 
     Ok(Json(Res {
+        results: merged,
+        total,
+        offset,
+        page_size: req.page_size,
         search_results: results,
+        engine_statuses,
     }))
 }
@@ -1,15 +1,33 @@
-use std::{fmt, net::IpAddr, str::FromStr, time::Duration};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
-use axum::Router;
+use axum::{Router, middleware::from_fn};
 use envconfig::Envconfig;
 use neo4rs::Graph;
 use oxalate_env::load_env_vars;
-use oxalate_init::{init_kafka_producer, init_logger, init_neo4j_pool, init_postgres_pool};
+use oxalate_init::{
+    init_kafka_producer, init_logger, init_neo4j_pool, init_postgres_pool, init_tracer,
+};
+use oxalate_middleware::{
+    auth_middleware::ApiKeys,
+    logging_middleware::logging_middleware,
+    metrics::{METRICS, QueryStore},
+    metrics_middleware::metrics_middleware,
+};
 use rdkafka::producer::FutureProducer;
 use sqlx::{Pool, Postgres};
 
 pub mod endpoints;
+pub mod page_rank;
+pub mod query;
 pub mod scraping;
+use page_rank::{PageRankConfig, update_page_ranks};
+use scraping::{CircuitBreakers, SearchEngineRegistry, SearchTimeouts};
 
 use tokio::time::sleep;
 use tower_http::cors::{Any, Cors, CorsLayer};
@@ -24,7 +42,9 @@ pub struct AppState {
     pub reqwest_client: reqwest::Client,
     pub wreq_client: wreq::Client,
     pub parser_url: Url,
+    pub search_engines: Arc<SearchEngineRegistry>,
     pub env_vars: &'static EnvVars,
+    pub api_keys: Arc<ApiKeys>,
 }
 
 #[derive(Envconfig)]
@@ -51,6 +71,10 @@ pub struct EnvVars {
     #[envconfig(from = "NEO4J_DNS")]
     pub neo4j_dns: String,
 
+    // tracing, spans go to an otlp/http collector, e.g. http://localhost:4318/v1/traces
+    #[envconfig(from = "OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>, // without it spans are not exported
+
     // Postgres
     #[envconfig(from = "POSTGRES_USER")]
     pub postgres_user: String,
@@ -76,6 +100,33 @@ pub struct EnvVars {
     pub indexer_bind_address: IpAddr,
     #[envconfig(from = "INDEXER_PORT", default = "22267")]
     pub indexer_port: u16,
+
+    // metasearch, every engine runs under its own timeout and circuit breaker
+    // available engines: brave, bing, google, oxalate, mock
+    #[envconfig(from = "SEARCH_ENGINES", default = "brave,bing,google,oxalate")]
+    pub search_engines: String,
+    #[envconfig(from = "EXTERNAL_ENGINE_TIMEOUT_MS", default = "4000")]
+    pub external_engine_timeout_ms: u64,
+    #[envconfig(from = "LOCAL_ENGINE_TIMEOUT_MS", default = "2000")]
+    pub local_engine_timeout_ms: u64,
+    #[envconfig(from = "CIRCUIT_BREAKER_FAILURES", default = "5")]
+    pub circuit_breaker_failures: u32,
+    #[envconfig(from = "CIRCUIT_BREAKER_COOLDOWN_SECS", default = "60")]
+    pub circuit_breaker_cooldown_secs: u64,
+
+    // page rank over the link graph, blended with bm25 by the oxalate engine
+    #[envconfig(from = "PAGE_RANK_INTERVAL_SECS", default = "3600")]
+    pub page_rank_interval_secs: u64,
+    #[envconfig(from = "PAGE_RANK_DAMPING", default = "0.85")]
+    pub page_rank_damping: f64,
+    #[envconfig(from = "PAGE_RANK_MAX_ITERATIONS", default = "50")]
+    pub page_rank_max_iterations: usize,
+    #[envconfig(from = "PAGE_RANK_AUTHORITY_WEIGHT", default = "0.3")]
+    pub page_rank_authority_weight: f32,
+
+    // keys allowed on /metrics as comma separated name:role:key, shared with the private harvester api
+    #[envconfig(from = "PRIVATE_API_KEYS")]
+    pub private_api_keys: String,
 }
 
 impl fmt::Debug for AppState {
@@ -111,6 +162,9 @@ async fn main() {
         producer.to_owned(),
     )
     .await;
+    // kept for the process lifetime, the servers here have no graceful shutdown to flush on
+    let _tracer_provider = init_tracer("oxalate-indexer", env_vars.otlp_endpoint.as_deref())
+        .expect("failed to init the tracer");
 
     let db_pool = init_postgres_pool(
         &env_vars.postgres_user,
@@ -158,6 +212,47 @@ async fn main() {
         }
     }
 
+    let search_engines = SearchEngineRegistry::from_config(
+        &env_vars.search_engines,
+        wreq_client.to_owned(),
+        db_pool.to_owned(),
+        env_vars.page_rank_authority_weight,
+        SearchTimeouts {
+            external: Duration::from_millis(env_vars.external_engine_timeout_ms),
+            local: Duration::from_millis(env_vars.local_engine_timeout_ms),
+        },
+        CircuitBreakers::new(
+            env_vars.circuit_breaker_failures,
+            Duration::from_secs(env_vars.circuit_breaker_cooldown_secs),
+        ),
+    )
+    .expect("invalid SEARCH_ENGINES config");
+    let search_engines = Arc::new(search_engines);
+
+    // create a bg thread for recomputing the page ranks
+    let page_rank_db_pool = db_pool.to_owned();
+    tokio::spawn(async move {
+        let config = PageRankConfig {
+            damping: env_vars.page_rank_damping,
+            max_iterations: env_vars.page_rank_max_iterations,
+            tolerance: 1e-6,
+        };
+        loop {
+            let res = METRICS
+                .time_query(
+                    QueryStore::Postgres,
+                    "update_page_ranks",
+                    update_page_ranks(&page_rank_db_pool, &config),
+                )
+                .await;
+            match res {
+                Ok(pages) => log::info!("updated the page rank of {pages} pages"),
+                Err(err) => log::error!("failed to update page ranks: {err:?}"),
+            }
+            sleep(Duration::from_secs(env_vars.page_rank_interval_secs)).await;
+        }
+    });
+
     let state = AppState {
         db_pool,
         kafka_producer_client: producer,
@@ -166,6 +261,11 @@ async fn main() {
         reqwest_client,
         env_vars,
         parser_url,
+        search_engines,
+        api_keys: Arc::new(
+            ApiKeys::from_config(&env_vars.private_api_keys)
+                .expect("refusing to start with a weak PRIVATE_API_KEYS key"),
+        ),
     };
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -175,6 +275,8 @@ async fn main() {
     let app = Router::new()
         .merge(endpoints::endpoints(&state))
         .with_state(state)
+        .layer(from_fn(metrics_middleware))
+        .layer(from_fn(logging_middleware))
         .layer(cors);
 
     let listener = tokio::net::TcpListener::bind(format!(
@@ -184,5 +286,10 @@ async fn main() {
     .await
     .unwrap();
     log::info!("server listening on {listener:?}");
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
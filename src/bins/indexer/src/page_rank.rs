@@ -0,0 +1,140 @@
+use std::{collections::HashMap, time::Instant};
+
+use exn::{Result, ResultExt};
+use futures::TryStreamExt;
+use sqlx::{Pool, Postgres};
+
+/// how many page ranks are written back per update query
+const WRITE_BATCH_SIZE: usize = 5_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load the link graph from db")]
+    LoadLinks,
+
+    #[error("failed to write the page ranks back into db")]
+    WriteRanks,
+}
+
+pub struct PageRankConfig {
+    pub damping: f64,
+    pub max_iterations: usize,
+    /// stops early once the L1 distance between two iterations drops below it
+    pub tolerance: f64,
+}
+
+/// Loads the `Links` table, runs page rank over it and writes the scores into
+/// `Webpages.page_rank`. Returns the amount of nodes in the link graph.
+pub async fn update_page_ranks(
+    db_pool: &Pool<Postgres>,
+    config: &PageRankConfig,
+) -> Result<usize, Error> {
+    let start = Instant::now();
+
+    let mut ids: HashMap<String, u32> = HashMap::new();
+    let mut urls: Vec<String> = vec![];
+    let mut edges: Vec<(u32, u32)> = vec![];
+    let mut intern = |url: String| -> u32 {
+        if let Some(id) = ids.get(&url) {
+            return *id;
+        }
+        let id = urls.len() as u32;
+        ids.insert(url.to_owned(), id);
+        urls.push(url);
+        id
+    };
+
+    let mut rows = sqlx::query!("SELECT source_url, target_url FROM Links;").fetch(db_pool);
+    while let Some(row) = rows.try_next().await.or_raise(|| Error::LoadLinks)? {
+        let source = intern(row.source_url);
+        let target = intern(row.target_url);
+        edges.push((source, target));
+    }
+    drop(rows);
+
+    let ranks = compute_page_rank(urls.len(), &edges, config);
+    log::info!(
+        "computed page rank over {} pages and {} links in {:?}",
+        urls.len(),
+        edges.len(),
+        start.elapsed()
+    );
+
+    // scaled by the node count so the mean is 1 no matter how big the graph gets
+    let scale = urls.len() as f64;
+    for (url_chunk, rank_chunk) in urls
+        .chunks(WRITE_BATCH_SIZE)
+        .zip(ranks.chunks(WRITE_BATCH_SIZE))
+    {
+        let rank_chunk = rank_chunk
+            .iter()
+            .map(|e| (e * scale) as f32)
+            .collect::<Vec<_>>();
+        sqlx::query!(
+            "
+                UPDATE Webpages AS w
+                SET page_rank = v.page_rank
+                FROM UNNEST($1::TEXT[], $2::REAL[]) AS v(url, page_rank)
+                WHERE w.url = v.url;
+            ",
+            url_chunk,
+            &rank_chunk,
+        )
+        .execute(db_pool)
+        .await
+        .or_raise(|| Error::WriteRanks)?;
+    }
+
+    Ok(urls.len())
+}
+
+/// Power iteration over `edges` between `node_count` nodes, the result sums up
+/// to 1. The rank of dangling nodes, pages without outgoing links which
+/// includes every page that was never crawled, is spread over all nodes.
+pub fn compute_page_rank(
+    node_count: usize,
+    edges: &[(u32, u32)],
+    config: &PageRankConfig,
+) -> Vec<f64> {
+    if node_count == 0 {
+        return vec![];
+    }
+
+    let n = node_count as f64;
+    let mut out_degree = vec![0u32; node_count];
+    for (source, _) in edges.iter() {
+        out_degree[*source as usize] += 1;
+    }
+
+    let mut ranks = vec![1.0 / n; node_count];
+    let mut next = vec![0.0; node_count];
+    for iteration in 0..config.max_iterations {
+        let dangling = ranks
+            .iter()
+            .zip(out_degree.iter())
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(rank, _)| rank)
+            .sum::<f64>();
+        let base = (1.0 - config.damping) / n + config.damping * dangling / n;
+        next.fill(base);
+
+        for (source, target) in edges.iter() {
+            let source = *source as usize;
+            next[*target as usize] += config.damping * ranks[source] / out_degree[source] as f64;
+        }
+
+        let delta = ranks
+            .iter()
+            .zip(next.iter())
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f64>();
+        std::mem::swap(&mut ranks, &mut next);
+
+        if delta < config.tolerance {
+            log::debug!("page rank converged after {} iterations", iteration + 1);
+            break;
+        }
+    }
+
+    ranks
+}
@@ -0,0 +1,128 @@
+use chrono::NaiveDate;
+use oxalate_parsing::tokenizer::{Language, Tokenizer};
+
+mod parse;
+pub use parse::parse_query;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("the query is empty")]
+    Empty,
+
+    #[error("unterminated quote starting at char {0}")]
+    UnterminatedQuote(usize),
+
+    #[error("`-` at char {0} is not followed by anything to exclude")]
+    DanglingExclusion(usize),
+
+    #[error("OR at char {0} needs something on both sides")]
+    DanglingOr(usize),
+
+    #[error("`{0}:` needs a value")]
+    MissingValue(&'static str),
+
+    #[error("`{0}:` can not be excluded")]
+    NotExcludable(&'static str),
+
+    #[error("`site:{0}` is not a valid host")]
+    InvalidSite(String),
+
+    #[error("`lang:{0}` is not a valid language tag")]
+    InvalidLang(String),
+
+    #[error("`{0}:{1}` is not a date, expected YYYY-MM-DD")]
+    InvalidDate(&'static str, String),
+
+    #[error("after:{after} and before:{before} leave no day to search in")]
+    EmptyDateRange { after: NaiveDate, before: NaiveDate },
+
+    #[error("a query needs a term, phrase or filter besides exclusions")]
+    OnlyExclusions,
+}
+
+/// A parsed search query, a disjunction of groups, every clause of a group has to match.
+///
+/// ```text
+/// rust "borrow checker" -tutorial site:doc.rust-lang.org OR title:lifetimes after:2024-01-01
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub groups: Vec<Group>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Group {
+    pub clauses: Vec<Clause>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    pub negated: bool,
+    pub kind: ClauseKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClauseKind {
+    Term(String),
+    Phrase(String),
+    /// lowercase host, also matches its subdomains
+    Site(String),
+    Title(String),
+    /// lowercase primary subtag of a BCP 47 tag
+    Lang(String),
+    /// created strictly before the day
+    Before(NaiveDate),
+    /// created strictly after the day
+    After(NaiveDate),
+}
+
+impl Query {
+    /// The words of every term and phrase that is not excluded, after the
+    /// tokenizer pipeline. Used to highlight and to build snippets.
+    pub fn positive_keywords(&self, tokenizer: &Tokenizer, lang: Language) -> Vec<String> {
+        let mut keywords = vec![];
+        for clause in self.groups.iter().flat_map(|e| e.clauses.iter()) {
+            if clause.negated {
+                continue;
+            }
+            if let ClauseKind::Term(text) | ClauseKind::Phrase(text) = &clause.kind {
+                for keyword in tokenizer.tokenize(text, lang) {
+                    if !keywords.contains(&keyword) {
+                        keywords.push(keyword);
+                    }
+                }
+            }
+        }
+        keywords
+    }
+
+    /// The query for external engines. They all understand phrases, exclusions,
+    /// `site:` and OR, the other filters only apply to our own index.
+    pub fn external_text(&self) -> String {
+        self.groups
+            .iter()
+            .map(|group| {
+                group
+                    .clauses
+                    .iter()
+                    .filter_map(|clause| {
+                        let text = match &clause.kind {
+                            ClauseKind::Term(text) => text.to_owned(),
+                            ClauseKind::Phrase(text) => format!("\"{text}\""),
+                            ClauseKind::Site(host) => format!("site:{host}"),
+                            _ => return None,
+                        };
+                        Some(if clause.negated {
+                            format!("-{text}")
+                        } else {
+                            text
+                        })
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|e| !e.is_empty())
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    }
+}
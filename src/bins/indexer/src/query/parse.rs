@@ -0,0 +1,219 @@
+use chrono::NaiveDate;
+use exn::{Exn, Result};
+use url::Host;
+
+use crate::query::{Clause, ClauseKind, Error, Group, Query};
+
+const FIELDS: [&str; 5] = ["site", "title", "lang", "before", "after"];
+
+/// Parses the query language of `/search`.
+///
+/// - words are combined with AND, `OR` (uppercase) splits the query into alternatives
+/// - `"..."` is a phrase, `-` in front of a word, phrase or filter excludes it
+/// - `site:`, `title:`, `lang:`, `before:` and `after:` filter, `title:"..."` takes a phrase
+/// - `before:` and `after:` take a `YYYY-MM-DD` day, both exclude the day itself
+///
+/// Unknown `field:value` pairs are searched as plain words.
+pub fn parse_query(input: &str) -> Result<Query, Error> {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut groups = vec![Group::default()];
+    let mut or_at = None;
+    let mut pos = 0;
+
+    loop {
+        while pos < chars.len() && chars[pos].is_whitespace() {
+            pos += 1;
+        }
+        if pos >= chars.len() {
+            break;
+        }
+
+        let start = pos;
+        let negated = chars[pos] == '-';
+        if negated {
+            pos += 1;
+            if pos >= chars.len() || chars[pos].is_whitespace() {
+                return Err(Exn::new(Error::DanglingExclusion(start)));
+            }
+        }
+
+        let kind = if chars[pos] == '"' {
+            let (phrase, next) = read_quoted(&chars, pos)?;
+            pos = next;
+            ClauseKind::Phrase(phrase)
+        } else {
+            let word_start = pos;
+            while pos < chars.len() && !chars[pos].is_whitespace() && chars[pos] != '"' {
+                pos += 1;
+            }
+            let word = chars[word_start..pos].iter().collect::<String>();
+
+            if !negated && word == "OR" {
+                let group = groups.last().expect("there is always a group");
+                if group.clauses.is_empty() {
+                    return Err(Exn::new(Error::DanglingOr(start)));
+                }
+                groups.push(Group::default());
+                or_at = Some(start);
+                continue;
+            }
+
+            match split_field(&word) {
+                Some((field, value)) => {
+                    let value = if value.is_empty() && pos < chars.len() && chars[pos] == '"' {
+                        let (quoted, next) = read_quoted(&chars, pos)?;
+                        pos = next;
+                        quoted
+                    } else {
+                        value.to_owned()
+                    };
+                    parse_field(field, value.trim(), negated)?
+                }
+                None => ClauseKind::Term(word),
+            }
+        };
+
+        // `""` and `title:""` style leftovers carry nothing to search for
+        if let ClauseKind::Phrase(text) | ClauseKind::Title(text) = &kind
+            && text.trim().is_empty()
+        {
+            continue;
+        }
+
+        groups
+            .last_mut()
+            .expect("there is always a group")
+            .clauses
+            .push(Clause { negated, kind });
+    }
+
+    if groups.len() > 1
+        && let Some(at) = or_at
+        && groups.last().is_some_and(|e| e.clauses.is_empty())
+    {
+        return Err(Exn::new(Error::DanglingOr(at)));
+    }
+    if groups.iter().all(|e| e.clauses.is_empty()) {
+        return Err(Exn::new(Error::Empty));
+    }
+
+    for group in groups.iter() {
+        validate_group(group)?;
+    }
+
+    Ok(Query { groups })
+}
+
+/// Returns the text between the quote at `start` and the closing one, and the position after it.
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize), Error> {
+    let Some(len) = chars[start + 1..].iter().position(|e| *e == '"') else {
+        return Err(Exn::new(Error::UnterminatedQuote(start)));
+    };
+    let end = start + 1 + len;
+    Ok((chars[start + 1..end].iter().collect(), end + 1))
+}
+
+fn split_field(word: &str) -> Option<(&'static str, &str)> {
+    let (field, value) = word.split_once(':')?;
+    let field = FIELDS.into_iter().find(|e| e.eq_ignore_ascii_case(field))?;
+    Some((field, value))
+}
+
+fn parse_field(field: &'static str, value: &str, negated: bool) -> Result<ClauseKind, Error> {
+    if value.is_empty() {
+        return Err(Exn::new(Error::MissingValue(field)));
+    }
+
+    let kind = match field {
+        "site" => ClauseKind::Site(parse_site(value)?),
+        "title" => ClauseKind::Title(value.to_owned()),
+        "lang" => {
+            let primary = value
+                .split(['-', '_'])
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if !(2..=3).contains(&primary.len())
+                || !primary.chars().all(|e| e.is_ascii_alphabetic())
+            {
+                return Err(Exn::new(Error::InvalidLang(value.to_owned())));
+            }
+            ClauseKind::Lang(primary)
+        }
+        "before" | "after" => {
+            if negated {
+                return Err(Exn::new(Error::NotExcludable(field)));
+            }
+            let Ok(day) = NaiveDate::parse_from_str(value, "%Y-%m-%d") else {
+                return Err(Exn::new(Error::InvalidDate(field, value.to_owned())));
+            };
+            if field == "before" {
+                ClauseKind::Before(day)
+            } else {
+                ClauseKind::After(day)
+            }
+        }
+        _ => unreachable!("field is one of FIELDS"),
+    };
+    Ok(kind)
+}
+
+/// Accepts a bare host as well as something pasted from the address bar like `https://example.com/path`.
+fn parse_site(value: &str) -> Result<String, Error> {
+    let without_scheme = value
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(value);
+    let host = without_scheme
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("*.")
+        .trim_end_matches('.');
+
+    let host = match Host::parse(host) {
+        Ok(Host::Domain(domain)) => domain,
+        Ok(ip) => ip.to_string(),
+        Err(_) => return Err(Exn::new(Error::InvalidSite(value.to_owned()))),
+    };
+    // the host ends up in a regex, so only what a hostname can contain gets through
+    let is_valid = !host.is_empty()
+        && !host.split('.').any(|e| e.is_empty())
+        && host
+            .chars()
+            .all(|e| e.is_ascii_alphanumeric() || matches!(e, '-' | '.' | '_'));
+    if !is_valid {
+        return Err(Exn::new(Error::InvalidSite(value.to_owned())));
+    }
+    Ok(host.to_ascii_lowercase())
+}
+
+fn validate_group(group: &Group) -> Result<(), Error> {
+    if group.clauses.iter().all(|e| e.negated) {
+        return Err(Exn::new(Error::OnlyExclusions));
+    }
+
+    let after = group
+        .clauses
+        .iter()
+        .filter_map(|e| match e.kind {
+            ClauseKind::After(day) => Some(day),
+            _ => None,
+        })
+        .max();
+    let before = group
+        .clauses
+        .iter()
+        .filter_map(|e| match e.kind {
+            ClauseKind::Before(day) => Some(day),
+            _ => None,
+        })
+        .min();
+    if let (Some(after), Some(before)) = (after, before)
+        && after.succ_opt().is_none_or(|next| next >= before)
+    {
+        return Err(Exn::new(Error::EmptyDateRange { after, before }));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+/// Per engine circuit breakers. After `failure_threshold` failures in a row the
+/// engine is skipped for `cooldown`, then a single request is let through again.
+#[derive(Debug)]
+pub struct CircuitBreakers {
+    failure_threshold: u32,
+    cooldown: Duration,
+    engines: Mutex<HashMap<&'static str, BreakerState>>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreakers {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            engines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns false while the breaker of the engine is open. Once the cooldown
+    /// passed the breaker is pushed forward again, so only one trial request runs.
+    pub fn allow(&self, engine: &'static str) -> bool {
+        let mut engines = self.engines.lock();
+        let state = engines.entry(engine).or_default();
+
+        let now = Instant::now();
+        match state.open_until {
+            Some(open_until) if open_until > now => false,
+            Some(_) => {
+                state.open_until = Some(now + self.cooldown);
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub fn record_success(&self, engine: &'static str) {
+        let mut engines = self.engines.lock();
+        let state = engines.entry(engine).or_default();
+        state.consecutive_failures = 0;
+        state.open_until = None;
+    }
+
+    pub fn record_failure(&self, engine: &'static str) {
+        let mut engines = self.engines.lock();
+        let state = engines.entry(engine).or_default();
+        state.consecutive_failures += 1;
+
+        if state.consecutive_failures >= self.failure_threshold {
+            log::warn!("opening the circuit breaker of search engine {engine}");
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
@@ -1,13 +1,48 @@
 use async_trait::async_trait;
 use exn::Result;
+use oxalate_parsing::tokenizer::Language;
+
+use crate::query::Query;
 
 pub mod image_search_engines;
 pub mod text_search_engines;
+use text_search_engines::TextSearchEngineResult;
+
+pub mod circuit_breaker;
+pub use circuit_breaker::CircuitBreakers;
+
+pub mod search_engine_registry;
+pub use search_engine_registry::{EngineStatus, SearchEngineRegistry, SearchTimeouts};
 
-mod search_text;
-pub use search_text::search_text;
+mod rank_fusion;
+pub use rank_fusion::reciprocal_rank_fusion;
+
+pub struct SearchQuery<'a> {
+    /// the query rendered for the external engines, see [`Query::external_text`]
+    pub text: &'a str,
+    /// the parsed query, local engines compile it into their own query language
+    pub parsed: &'a Query,
+    /// language of the query, for the tokenizer pipeline of local engines
+    pub lang: Language,
+    /// how many results local engines should return at most
+    pub limit: usize,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("search engine {0} failed")]
+    Engine(&'static str),
+}
 
 #[async_trait]
-pub trait SearchEngine<SearchEngineResult, Args, Error: std::error::Error + Sync + Send> {
-    async fn search(query: &str, args: Args) -> Result<Vec<SearchEngineResult>, Error>;
+pub trait SearchEngine: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// local engines search our own data, they run under the local timeout and
+    /// their results are not sent back to the parser as meta webpages
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    async fn search(&self, query: &SearchQuery<'_>) -> Result<Vec<TextSearchEngineResult>, Error>;
 }
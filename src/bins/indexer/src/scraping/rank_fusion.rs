@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use oxalate_parsing::canonicalize_url::UrlCanonicalizer;
+use oxalate_schemas::indexer::post_search::RankedSearchResult;
+
+use crate::scraping::text_search_engines::TextSearchEngineResult;
+
+/// Dampens the advantage of the very first ranks, 60 is the value from the original paper.
+const RRF_K: f32 = 60.0;
+
+/// Merges the results of every engine into one list with weighted reciprocal
+/// rank fusion. Results are deduplicated on their canonical url, the title and
+/// text come from the engine that ranked the result the highest.
+pub fn reciprocal_rank_fusion(
+    results: &HashMap<&'static str, Vec<TextSearchEngineResult>>,
+    weights: &HashMap<String, f32>,
+    canonicalizer: &UrlCanonicalizer,
+) -> Vec<RankedSearchResult> {
+    struct Fused<'a> {
+        result: &'a TextSearchEngineResult,
+        best_contribution: f32,
+        score: f32,
+        sources: Vec<String>,
+    }
+
+    let mut fused: HashMap<String, Fused> = HashMap::new();
+    for (engine, engine_results) in results.iter() {
+        let weight = weights.get(*engine).copied().unwrap_or(1.0);
+        if weight <= 0.0 {
+            continue;
+        }
+
+        for (rank, result) in engine_results.iter().enumerate() {
+            let key = canonicalizer
+                .canonicalize(&result.url)
+                .unwrap_or_else(|| result.url.to_owned())
+                .to_string();
+            let contribution = weight / (RRF_K + rank as f32 + 1.0);
+
+            let entry = fused.entry(key).or_insert_with(|| Fused {
+                result,
+                best_contribution: contribution,
+                score: 0.0,
+                sources: vec![],
+            });
+
+            entry.score += contribution;
+            if !entry.sources.iter().any(|e| e == engine) {
+                entry.sources.push(engine.to_string());
+            }
+            if contribution > entry.best_contribution {
+                entry.best_contribution = contribution;
+                entry.result = result;
+            }
+        }
+    }
+
+    let mut ranked = fused
+        .into_values()
+        .map(|mut e| {
+            e.sources.sort();
+            RankedSearchResult {
+                url: e.result.url.to_owned(),
+                text: e.result.text.to_owned(),
+                title: e.result.title.to_owned(),
+                score: e.score,
+                sources: e.sources,
+                highlights: vec![],
+            }
+        })
+        .collect::<Vec<_>>();
+
+    ranked.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.url.as_str().cmp(b.url.as_str()))
+    });
+    ranked
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use exn::{Exn, Result};
+use futures::future::join_all;
+use oxalate_middleware::metrics::{METRICS, SearchOutcome};
+use sqlx::{Pool, Postgres};
+use wreq::Client;
+
+use crate::scraping::{
+    SearchEngine, SearchQuery,
+    circuit_breaker::CircuitBreakers,
+    text_search_engines::{
+        TextSearchEngineResult, bing::TextSearchBing, brave::TextSearchBrave,
+        google::TextSearchGoogle, mock::TextSearchMock, oxalate::TextSearchOxalate,
+    },
+};
+
+pub struct EngineOutcome {
+    pub results: Vec<TextSearchEngineResult>,
+    pub status: EngineStatus,
+    pub latency: Duration,
+    pub local: bool,
+}
+
+pub enum EngineStatus {
+    Ok,
+    Timeout,
+    Error(String),
+    CircuitOpen,
+}
+
+pub struct SearchTimeouts {
+    pub external: Duration,
+    pub local: Duration,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("unknown search engine {0}")]
+    UnknownEngine(String),
+
+    #[error("search engine {0} is registered twice")]
+    DuplicateEngine(&'static str),
+}
+
+/// Every enabled search engine, built once at startup from the `SEARCH_ENGINES` env var.
+pub struct SearchEngineRegistry {
+    engines: Vec<Arc<dyn SearchEngine>>,
+    timeouts: SearchTimeouts,
+    breakers: CircuitBreakers,
+}
+
+impl SearchEngineRegistry {
+    pub fn new(timeouts: SearchTimeouts, breakers: CircuitBreakers) -> Self {
+        Self {
+            engines: vec![],
+            timeouts,
+            breakers,
+        }
+    }
+
+    /// Builds the registry from a comma separated list of engine names like `brave,oxalate`.
+    pub fn from_config(
+        enabled_engines: &str,
+        wreq_client: Client,
+        db_pool: Pool<Postgres>,
+        authority_weight: f32,
+        timeouts: SearchTimeouts,
+        breakers: CircuitBreakers,
+    ) -> Result<Self, Error> {
+        let mut registry = Self::new(timeouts, breakers);
+        for name in enabled_engines
+            .split(',')
+            .map(|e| e.trim().to_lowercase())
+            .filter(|e| !e.is_empty())
+        {
+            let engine: Arc<dyn SearchEngine> = match name.as_str() {
+                "brave" => Arc::new(TextSearchBrave::new(wreq_client.to_owned())),
+                "bing" => Arc::new(TextSearchBing::new(wreq_client.to_owned())),
+                "google" => Arc::new(TextSearchGoogle::new(wreq_client.to_owned())),
+                "oxalate" => Arc::new(TextSearchOxalate::new(db_pool.to_owned(), authority_weight)),
+                "mock" => Arc::new(TextSearchMock::new()),
+                _ => return Err(Exn::new(Error::UnknownEngine(name))),
+            };
+            registry.register(engine)?;
+        }
+
+        Ok(registry)
+    }
+
+    pub fn register(&mut self, engine: Arc<dyn SearchEngine>) -> Result<(), Error> {
+        if self.get(engine.name()).is_some() {
+            return Err(Exn::new(Error::DuplicateEngine(engine.name())));
+        }
+        log::info!("registered search engine {}", engine.name());
+        self.engines.push(engine);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn SearchEngine>> {
+        self.engines.iter().find(|e| e.name() == name)
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.engines.iter().map(|e| e.name()).collect()
+    }
+
+    /// Fans the query out to the chosen engines, or to all of them when `engines`
+    /// is `None`. An engine that fails, times out or has an open circuit breaker
+    /// only shows up in its status, the others still count.
+    pub async fn search(
+        &self,
+        query: &SearchQuery<'_>,
+        engines: Option<&[String]>,
+    ) -> Result<HashMap<&'static str, EngineOutcome>, Error> {
+        let selected = match engines {
+            Some(names) => {
+                let mut selected = vec![];
+                for name in names {
+                    match self.get(name) {
+                        Some(engine) => selected.push(engine),
+                        None => return Err(Exn::new(Error::UnknownEngine(name.to_owned()))),
+                    }
+                }
+                selected
+            }
+            None => self.engines.iter().collect(),
+        };
+
+        let outcomes = join_all(
+            selected
+                .into_iter()
+                .map(|engine| self.run_engine(engine.as_ref(), query)),
+        )
+        .await;
+
+        Ok(outcomes.into_iter().collect())
+    }
+
+    async fn run_engine(
+        &self,
+        engine: &dyn SearchEngine,
+        query: &SearchQuery<'_>,
+    ) -> (&'static str, EngineOutcome) {
+        let name = engine.name();
+        let local = engine.is_local();
+        if !self.breakers.allow(name) {
+            METRICS.record_search(name, SearchOutcome::Skipped);
+            let outcome = EngineOutcome {
+                results: vec![],
+                status: EngineStatus::CircuitOpen,
+                latency: Duration::ZERO,
+                local,
+            };
+            return (name, outcome);
+        }
+
+        let timeout = if local {
+            self.timeouts.local
+        } else {
+            self.timeouts.external
+        };
+
+        let start = Instant::now();
+        let res = tokio::time::timeout(timeout, engine.search(query)).await;
+        let latency = start.elapsed();
+
+        let (results, status) = match res {
+            Ok(Ok(results)) => {
+                self.breakers.record_success(name);
+                METRICS.record_search(name, SearchOutcome::Success);
+                (results, EngineStatus::Ok)
+            }
+            Ok(Err(err)) => {
+                log::warn!("search engine {name} failed: {err:?}");
+                self.breakers.record_failure(name);
+                METRICS.record_search(name, SearchOutcome::Failure);
+                (vec![], EngineStatus::Error(err.to_string()))
+            }
+            Err(_) => {
+                log::warn!("search engine {name} timed out after {timeout:?}");
+                self.breakers.record_failure(name);
+                METRICS.record_search(name, SearchOutcome::Timeout);
+                (vec![], EngineStatus::Timeout)
+            }
+        };
+
+        let outcome = EngineOutcome {
+            results,
+            status,
+            latency,
+            local,
+        };
+        (name, outcome)
+    }
+}
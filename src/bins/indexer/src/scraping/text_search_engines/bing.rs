@@ -6,10 +6,13 @@ use scraper::{ElementRef, Html, Node, Selector};
 use url::Url;
 use wreq::Client;
 
-use crate::scraping::{SearchEngine, text_search_engines::TextSearchEngineResult};
+use crate::scraping::{
+    self, SearchEngine, SearchQuery, text_search_engines::TextSearchEngineResult,
+};
 
-#[derive(Hash, Eq, PartialEq)]
-pub struct TextSearchBing;
+pub struct TextSearchBing {
+    wreq_client: Client,
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -21,9 +24,28 @@ pub enum Error {
 }
 
 #[async_trait]
-impl SearchEngine<TextSearchEngineResult, Client, Error> for TextSearchBing {
-    async fn search(query: &str, args: Client) -> Result<Vec<TextSearchEngineResult>, Error> {
-        let wreq_client = args;
+impl SearchEngine for TextSearchBing {
+    fn name(&self) -> &'static str {
+        "bing"
+    }
+
+    async fn search(
+        &self,
+        query: &SearchQuery<'_>,
+    ) -> Result<Vec<TextSearchEngineResult>, scraping::Error> {
+        self.fetch_results(query.text)
+            .await
+            .or_raise(|| scraping::Error::Engine(self.name()))
+    }
+}
+
+impl TextSearchBing {
+    pub fn new(wreq_client: Client) -> Self {
+        Self { wreq_client }
+    }
+
+    async fn fetch_results(&self, query: &str) -> Result<Vec<TextSearchEngineResult>, Error> {
+        let wreq_client = &self.wreq_client;
         let cvid = generate_cvid();
 
         let res = wreq_client
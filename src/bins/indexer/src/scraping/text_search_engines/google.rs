@@ -4,10 +4,13 @@ use scraper::{ElementRef, Html, Selector};
 use url::Url;
 use wreq::Client;
 
-use crate::scraping::{SearchEngine, text_search_engines::TextSearchEngineResult};
+use crate::scraping::{
+    self, SearchEngine, SearchQuery, text_search_engines::TextSearchEngineResult,
+};
 
-#[derive(Hash, Eq, PartialEq)]
-pub struct TextSearchGoogle;
+pub struct TextSearchGoogle {
+    wreq_client: Client,
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -19,9 +22,28 @@ pub enum Error {
 }
 
 #[async_trait]
-impl SearchEngine<TextSearchEngineResult, Client, Error> for TextSearchGoogle {
-    async fn search(query: &str, args: Client) -> Result<Vec<TextSearchEngineResult>, Error> {
-        let wreq_client = args;
+impl SearchEngine for TextSearchGoogle {
+    fn name(&self) -> &'static str {
+        "google"
+    }
+
+    async fn search(
+        &self,
+        query: &SearchQuery<'_>,
+    ) -> Result<Vec<TextSearchEngineResult>, scraping::Error> {
+        self.fetch_results(query.text)
+            .await
+            .or_raise(|| scraping::Error::Engine(self.name()))
+    }
+}
+
+impl TextSearchGoogle {
+    pub fn new(wreq_client: Client) -> Self {
+        Self { wreq_client }
+    }
+
+    async fn fetch_results(&self, query: &str) -> Result<Vec<TextSearchEngineResult>, Error> {
+        let wreq_client = &self.wreq_client;
 
         let res = wreq_client
             .get("https://www.google.com/search")
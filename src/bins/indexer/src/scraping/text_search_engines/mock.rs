@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use exn::Result;
+use url::Url;
+
+use crate::scraping::{
+    self, SearchEngine, SearchQuery, text_search_engines::TextSearchEngineResult,
+};
+
+const MOCK_RESULTS: usize = 10;
+
+/// Returns made up but deterministic results without touching the network or
+/// the db, so the indexer can be run and tested offline.
+#[derive(Default)]
+pub struct TextSearchMock;
+
+impl TextSearchMock {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl SearchEngine for TextSearchMock {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    async fn search(
+        &self,
+        query: &SearchQuery<'_>,
+    ) -> Result<Vec<TextSearchEngineResult>, scraping::Error> {
+        let mut base = Url::parse("https://mock.oxalate.invalid/search").unwrap();
+        base.query_pairs_mut().append_pair("q", query.text);
+
+        let results = (0..MOCK_RESULTS.min(query.limit.max(1)))
+            .map(|i| {
+                let mut url = base.to_owned();
+                url.query_pairs_mut().append_pair("n", &i.to_string());
+
+                TextSearchEngineResult {
+                    url,
+                    title: format!("Mock result {} for {}", i + 1, query.text),
+                    text: format!(
+                        "This is mock result number {} for the query \"{}\".",
+                        i + 1,
+                        query.text
+                    ),
+                }
+            })
+            .collect();
+
+        Ok(results)
+    }
+}
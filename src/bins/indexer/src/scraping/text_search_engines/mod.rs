@@ -4,4 +4,5 @@ pub use text_search_engine_result::TextSearchEngineResult;
 pub mod bing;
 pub mod brave;
 pub mod google;
+pub mod mock;
 pub mod oxalate;
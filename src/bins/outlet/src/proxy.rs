@@ -7,10 +7,17 @@ use futures::future;
 use futures::stream::{self, StreamExt};
 use log::{error, info};
 use oxalate_schemas::harvester::public::proxy::post_proxy::{Req, Res};
-use oxalate_scraper_controller::scraper_controller::{HttpRes, ProxyReq, ProxyRes};
-use reqwest::{Client, Url};
+use oxalate_scraper_controller::scraper_controller::{
+    FetchStatus, HttpMethod, HttpReq, HttpRes, ProxyReq, ProxyRes, RedirectHop,
+};
+use reqwest::{
+    Client, Method, Response, StatusCode,
+    header::{CONTENT_TYPE, IF_MODIFIED_SINCE, IF_NONE_MATCH, LOCATION},
+};
 use tokio::time::sleep;
 
+const MAX_REDIRECTS: usize = 10;
+
 pub fn proxy(reqwest_client: Client, global_state: AppState) {
     tokio::spawn(async move {
         let url = format!(
@@ -19,8 +26,10 @@ pub fn proxy(reqwest_client: Client, global_state: AppState) {
         );
         loop {
             info!("requesting urls");
+            // the token only goes to the harvester, never to the crawled sites
             let res = match reqwest_client
                 .post(&url)
+                .bearer_auth(&global_state.env_vars.worker_token)
                 .json(&Req::RequestUrls)
                 .send()
                 .await
@@ -65,8 +74,9 @@ pub fn proxy(reqwest_client: Client, global_state: AppState) {
                     async {
                         match req {
                             ProxyReq::Http(e) => {
-                                handle_http_https_request(&reqwest_client, e.url, &global_state)
-                                    .await
+                                // spaces out the reqs to one host by its crawl-delay
+                                sleep(Duration::from_millis(e.delay_ms)).await;
+                                handle_http_https_request(&reqwest_client, e, &global_state).await
                             }
                         }
                     }
@@ -83,7 +93,13 @@ pub fn proxy(reqwest_client: Client, global_state: AppState) {
 
             let req = Req::ReturnUrlOutputs(outputs);
 
-            if let Err(err) = reqwest_client.post(&url).json(&req).send().await {
+            if let Err(err) = reqwest_client
+                .post(&url)
+                .bearer_auth(&global_state.env_vars.worker_token)
+                .json(&req)
+                .send()
+                .await
+            {
                 info!("failed to send back http outputs!: {err}");
                 sleep(Duration::from_secs(30)).await;
             };
@@ -139,25 +155,17 @@ pub fn proxy(reqwest_client: Client, global_state: AppState) {
 
 async fn handle_http_https_request(
     reqwest_client: &Client,
-    url: Url,
+    req: HttpReq,
     global_state: &AppState,
 ) -> Option<Box<ProxyRes>> {
     // dbg!(&url);
-    let res = reqwest_client
-        .get(url.as_str())
-        .header("machine-id", "")
-        .send()
-        .await;
-    global_state
-        .request_counter
-        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let res = send_following_redirects(reqwest_client, &req, global_state).await;
 
     match res {
-        Ok(e) => {
+        Ok((e, redirects)) => {
             info!("website hit");
             let status = e.status().as_u16();
             let raw_headers = e.headers().to_owned();
-            let body = e.text().await.unwrap_or_default();
 
             let mut headers = HashMap::with_capacity(raw_headers.len());
             for (key, val) in raw_headers.iter() {
@@ -170,11 +178,31 @@ async fn handle_http_https_request(
                 headers.insert(key.to_string(), val.to_string());
             }
 
+            let content_type = raw_headers
+                .get(CONTENT_TYPE)
+                .and_then(|e| e.to_str().ok())
+                .map(|e| e.to_owned());
+            let max_body_bytes = req.limits.max_body_bytes;
+            let (body, fetch_status) = if e.status() == StatusCode::NOT_MODIFIED {
+                (vec![], FetchStatus::NotModified)
+            } else if !req.limits.accepts_content_type(content_type.as_deref()) {
+                (vec![], FetchStatus::SkippedContentType)
+            } else if let (Some(max), Some(len)) = (max_body_bytes, e.content_length())
+                && len > max
+            {
+                (vec![], FetchStatus::SkippedTooLarge)
+            } else {
+                read_body(e, max_body_bytes).await
+            };
+
             let proxy_output = HttpRes {
-                url,
+                url: req.url,
                 status,
                 body,
                 headers,
+                content_type,
+                fetch_status,
+                redirects,
             };
             let proxy_output = ProxyRes::HttpRes(proxy_output);
 
@@ -191,3 +219,113 @@ async fn handle_http_https_request(
         }
     }
 }
+
+/// Sends the req as asked and follows redirects by hand, reqwest's own policy
+/// would not tell us which urls we went through. Only redirects within the origin
+/// of the req are followed, so its headers and validators stay meant for the
+/// server they go to. A redirect to another origin is returned as is, the
+/// harvester crawls its target as a req of its own, through robots.txt and the
+/// host scheduler.
+async fn send_following_redirects(
+    reqwest_client: &Client,
+    req: &HttpReq,
+    global_state: &AppState,
+) -> reqwest::Result<(Response, Vec<RedirectHop>)> {
+    let mut url = req.url.to_owned();
+    let mut method = reqwest_method(&req.method);
+    let mut body = Some(req.body.to_owned()).filter(|e| !e.is_empty());
+    let mut redirects = vec![];
+
+    loop {
+        let mut builder = reqwest_client
+            .request(method.to_owned(), url.as_str())
+            .header("machine-id", "");
+        for (key, val) in req.headers.iter() {
+            builder = builder.header(key, val);
+        }
+        if let Some(ref etag) = req.validators.etag {
+            builder = builder.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(ref last_modified) = req.validators.last_modified {
+            builder = builder.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        if let Some(ref body) = body {
+            builder = builder.body(body.to_owned());
+        }
+
+        let res = builder.send().await;
+        global_state
+            .request_counter
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let res = res?;
+
+        let status = res.status();
+        // 304 is a 3xx too but it is an answer, not a redirect
+        let is_redirect = matches!(status.as_u16(), 301 | 302 | 303 | 307 | 308);
+        let location = res
+            .headers()
+            .get(LOCATION)
+            .and_then(|e| e.to_str().ok())
+            .and_then(|e| url.join(e).ok());
+        let Some(location) = location.filter(|e| {
+            is_redirect && redirects.len() < MAX_REDIRECTS && e.origin() == req.url.origin()
+        }) else {
+            return Ok((res, redirects));
+        };
+
+        // like browsers, everything but 307 and 308 turns into a bodyless GET
+        if !matches!(status.as_u16(), 307 | 308) && method != Method::HEAD {
+            method = Method::GET;
+            body = None;
+        }
+        redirects.push(RedirectHop {
+            status: status.as_u16(),
+            location: location.to_owned(),
+        });
+        url = location;
+    }
+}
+
+fn reqwest_method(method: &HttpMethod) -> Method {
+    match method {
+        HttpMethod::Get => Method::GET,
+        HttpMethod::Head => Method::HEAD,
+        HttpMethod::Post => Method::POST,
+        HttpMethod::Put => Method::PUT,
+        HttpMethod::Delete => Method::DELETE,
+        HttpMethod::Connect => Method::CONNECT,
+        HttpMethod::Options => Method::OPTIONS,
+        HttpMethod::Trace => Method::TRACE,
+        HttpMethod::Patch => Method::PATCH,
+    }
+}
+
+// streams the body chunk by chunk so a missing or lying content length can't make us
+// download more than max_bytes
+async fn read_body(mut res: Response, max_bytes: Option<u64>) -> (Vec<u8>, FetchStatus) {
+    let max_bytes = max_bytes.map(|e| e as usize).unwrap_or(usize::MAX);
+    let mut body = Vec::new();
+    let mut fetch_status = FetchStatus::Complete;
+
+    loop {
+        let chunk = match res.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            // the connection broke off, what arrived so far is not the whole body
+            Err(err) => {
+                error!("failed to read the body of {}: {err}", res.url());
+                fetch_status = FetchStatus::Truncated;
+                break;
+            }
+        };
+        let room = max_bytes - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            fetch_status = FetchStatus::Truncated;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    (body, fetch_status)
+}
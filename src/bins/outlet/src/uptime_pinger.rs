@@ -1,6 +1,8 @@
 use crate::AppState;
 use futures::{SinkExt, StreamExt};
-use reqwest::header::HeaderValue;
+use reqwest::header::{
+    CONNECTION, HOST, HeaderValue, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE,
+};
 use std::time::Duration;
 use tokio::time::sleep;
 use tokio_tungstenite::{
@@ -15,19 +17,23 @@ pub fn uptime_pinger(global_state: AppState) {
             "ws://{}:{}/info/uptime",
             global_state.env_vars.harvester_dns, global_state.env_vars.public_harvester_port
         );
-        let request = Request::builder()
-            .uri(&url)
-            .header("Host", global_state.env_vars.harvester_dns.to_owned())
-            .header("Connection", "Upgrade")
-            .header("Upgrade", "websocket")
-            .header("Sec-WebSocket-Version", "13")
-            .header("Sec-WebSocket-Key", generate_key())
+        // reqwest only builds the handshake request here, tungstenite sends it
+        let request = reqwest::Client::new()
+            .get(&url)
+            .header(HOST, global_state.env_vars.harvester_dns.to_owned())
+            .header(CONNECTION, "Upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_VERSION, "13")
+            .header(SEC_WEBSOCKET_KEY, generate_key())
             .header(
                 "machine-id",
                 HeaderValue::from_str(global_state.machine_id).unwrap(),
             )
-            .body(())
-            .expect("failed to build static uptime pinger request body");
+            .bearer_auth(&global_state.env_vars.worker_token)
+            .build()
+            .and_then(Request::<reqwest::Body>::try_from)
+            .expect("failed to build static uptime pinger request")
+            .map(|_| ());
 
         loop {
             let ws_stream = match connect_async(request.to_owned()).await {
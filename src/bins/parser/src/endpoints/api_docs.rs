@@ -1,5 +1,6 @@
 use utoipa::OpenApi;
 
+use crate::endpoints::get_ingest_metrics;
 use crate::endpoints::get_ping;
 use crate::endpoints::post_insert_meta_webpage;
 use crate::endpoints::post_insert_webpage;
@@ -8,6 +9,7 @@ use crate::endpoints::post_insert_webpage;
 #[openapi(
     paths(
         get_ping::get_ping,
+        get_ingest_metrics::get_ingest_metrics,
         post_insert_meta_webpage::post_insert_meta_webpage,
         post_insert_webpage::post_insert_webpage,
     ),
@@ -0,0 +1,29 @@
+use axum::{Json, extract::State};
+
+use crate::AppState;
+use oxalate_schemas::parser::get_ingest_metrics::*;
+
+#[utoipa::path(
+    get,
+    path = "/ingest_metrics",
+    responses(
+        (status = 200),
+    ),
+    description = "Throughput of the batched neo4j keyword graph ingestion",
+    tag = "Metric",
+)]
+#[axum::debug_handler]
+pub async fn get_ingest_metrics(State(state): State<AppState>) -> Json<Res> {
+    let metrics = state.neo4j_ingestor.metrics();
+
+    Json(Res {
+        pages_ingested: metrics.pages_ingested,
+        batches: metrics.batches,
+        failed_batches: metrics.failed_batches,
+        words_written: metrics.words_written,
+        pairs_written: metrics.pairs_written,
+        pages_per_sec: metrics.pages_per_sec,
+        last_batch_ms: metrics.last_batch_ms,
+        queued_pages: metrics.queued_pages,
+    })
+}
@@ -1,7 +1,9 @@
 use axum::{
     Router,
+    middleware::from_fn_with_state,
     routing::{get, post},
 };
+use oxalate_middleware::{auth_middleware::auth_middleware, metrics::get_metrics};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -12,15 +14,26 @@ pub mod api_docs;
 pub mod get_ping;
 use get_ping::get_ping;
 
+pub mod get_ingest_metrics;
+use get_ingest_metrics::get_ingest_metrics;
+
 pub mod post_insert_meta_webpage;
 use post_insert_meta_webpage::post_insert_meta_webpage;
 
 pub mod post_insert_webpage;
 use post_insert_webpage::post_insert_webpage;
 
-pub fn endpoints(_state: &AppState) -> Router<AppState> {
+pub fn endpoints(state: &AppState) -> Router<AppState> {
     Router::new()
         .route("/ping", get(get_ping))
+        .route(
+            "/metrics",
+            get(get_metrics).layer(from_fn_with_state(
+                state.api_keys.to_owned(),
+                auth_middleware,
+            )),
+        )
+        .route("/ingest_metrics", get(get_ingest_metrics))
         .route("/insert_meta_webpage", post(post_insert_meta_webpage))
         .route("/insert_webpage", post(post_insert_webpage))
         .merge(SwaggerUi::new("/swagger").url("/api-docs/openapi.json", ApiDoc::openapi()))
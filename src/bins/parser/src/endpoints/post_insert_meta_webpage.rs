@@ -2,14 +2,14 @@ use std::sync::Arc;
 
 use axum::{Json, extract::State};
 use exn::ResultExt;
+use futures::{StreamExt, TryStreamExt, stream};
 use http_error::HttpError;
+use oxalate_middleware::metrics::{METRICS, QueryStore};
 use oxalate_parsing::save_meta_webpage_into_postgres::save_meta_webpage_into_postgres;
 
 use crate::AppState;
 use oxalate_schemas::parser::post_insert_meta_webpage::*;
 
-pub use oxalate_parsing::save_into_neo4j::save_into_neo4j;
-
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("failed to insert parsed html into neo4j")]
@@ -33,25 +33,39 @@ pub async fn post_insert_meta_webpage(
     State(state): State<AppState>,
     Json(req): Json<Req>,
 ) -> Result<(), HttpError> {
-    for page in req.pages {
-        let pg_result = save_meta_webpage_into_postgres(
-            &state.db_pool,
-            &page.keywords,
-            &page.title,
-            &page.url,
-            &page.search_engine,
+    // a few at once, so their neo4j keywords share a batch instead of each waiting for the flush
+    stream::iter(req.pages)
+        .map(|page| insert_meta_webpage(&state, page))
+        .buffer_unordered(state.insert_concurrency)
+        .try_collect::<Vec<_>>()
+        .await
+        .or_raise(|| HttpError::Internal("".into()))?;
+
+    Ok(())
+}
+
+async fn insert_meta_webpage(state: &AppState, page: Page) -> exn::Result<(), Error> {
+    let url = state.url_canonicalizer.canonical_page_url(&page.url, None);
+
+    let pg_result = METRICS
+        .time_query(
+            QueryStore::Postgres,
+            "save_meta_webpage",
+            save_meta_webpage_into_postgres(
+                &state.db_pool,
+                &page.keywords,
+                &page.title,
+                &url,
+                &page.search_engine,
+                &state.url_canonicalizer,
+            ),
         )
         .await;
 
-        let neo4j_result = save_into_neo4j(&state.neo4j_pool, &page.keywords, &page.url, 5).await;
+    let neo4j_result = state.neo4j_ingestor.ingest(&url, page.keywords).await;
 
-        neo4j_result
-            .or_raise(|| Error::InsertNeo4j)
-            .or_raise(|| HttpError::Internal("".into()))?;
-        pg_result
-            .or_raise(|| Error::InsertPg)
-            .or_raise(|| HttpError::Internal("".into()))?;
-    }
+    neo4j_result.or_raise(|| Error::InsertNeo4j)?;
+    pg_result.or_raise(|| Error::InsertPg)?;
 
     Ok(())
 }
@@ -1,17 +1,25 @@
 use axum::{Json, extract::State};
 use exn::ResultExt;
 use http_error::HttpError;
-use oxalate_parsing::save_parsed_webpage_into_postgres::save_parsed_webpage_into_postgres;
+use oxalate_middleware::metrics::{METRICS, QueryStore};
+use oxalate_parsing::save_parsed_webpage_into_postgres::{
+    PageSource, save_parsed_webpage_into_postgres,
+};
 
 use crate::AppState;
 use oxalate_schemas::parser::post_insert_webpage::*;
 
 pub use oxalate_parsing::{
-    compress_html::compress_html, parse_html::parse_html, save_into_neo4j::save_into_neo4j,
+    compress_html::compress_html,
+    decode_body::{decode_body, is_textual},
+    parse_html::parse_html,
 };
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    #[error("failed to serialize the headers")]
+    Headers,
+
     #[error("failed parse html")]
     Parse,
 
@@ -39,42 +47,75 @@ pub async fn post_insert_webpage(
     State(state): State<AppState>,
     Json(req): Json<Req>,
 ) -> Result<(), HttpError> {
-    for page in req.pages {
-        if page.raw_html.is_empty() {
-            return Err(HttpError::BadRequest("empty html field!".to_owned()));
+    for page in req.pages.iter() {
+        if page.body.is_empty() {
+            return Err(HttpError::BadRequest("empty body field!".to_owned()));
         }
+    }
 
-        let headers = serde_json::to_value(page.headers.unwrap_or_default())
-            .or_raise(|| HttpError::BadRequest("invalid headers".into()))?;
-
-        let compressed_html = compress_html(&page.raw_html)
-            .or_raise(|| Error::Compress)
-            .or_raise(|| HttpError::Internal("".into()))?;
-
-        let parsed_html = parse_html(page.raw_html, page.url.to_owned())
+    for page in req.pages {
+        insert_webpage(&state, page)
             .await
-            .or_raise(|| Error::Parse)
             .or_raise(|| HttpError::Internal("".into()))?;
+    }
 
-        // we dont joint the db futures, bc it will send such a high amount of request to the databases that there is a real risk they can crash
-        let neo4j_result =
-            save_into_neo4j(&state.neo4j_pool, &parsed_html.keywords, &page.url, 5).await;
-        let pg_result = save_parsed_webpage_into_postgres(
-            &state.db_pool,
-            &parsed_html,
-            &compressed_html,
-            headers,
-            page.proxy_id,
-            page.url,
+    Ok(())
+}
+
+/// Parses and stores one page, shared by this endpoint and the proxy res consumer.
+pub async fn insert_webpage(state: &AppState, page: Page) -> exn::Result<(), Error> {
+    let content_type = page.content_type.as_deref();
+    if !is_textual(content_type) {
+        log::debug!(
+            "not indexing {} bc its content type {content_type:?} is not text",
+            page.url
+        );
+        return Ok(());
+    }
+    let html = decode_body(&page.body, content_type);
+
+    let headers =
+        serde_json::to_value(page.headers.unwrap_or_default()).or_raise(|| Error::Headers)?;
+
+    let compressed_html = compress_html(&html).or_raise(|| Error::Compress)?;
+
+    let parsed_html = parse_html(html, page.url.to_owned())
+        .await
+        .or_raise(|| Error::Parse)?;
+
+    let url = state
+        .url_canonicalizer
+        .canonical_page_url(&page.url, parsed_html.canonical_url.as_ref());
+
+    // we dont joint the db futures, bc it will send such a high amount of request to the databases that there is a real risk they can crash
+    let neo4j_result = state
+        .neo4j_ingestor
+        .ingest(&url, parsed_html.keywords.to_owned())
+        .await;
+    let pg_result = METRICS
+        .time_query(
+            QueryStore::Postgres,
+            "save_parsed_webpage",
+            save_parsed_webpage_into_postgres(
+                &state.db_pool,
+                &parsed_html,
+                &compressed_html,
+                headers,
+                PageSource {
+                    proxy_id: page.proxy_id,
+                    url: page.url,
+                    requested_url: page.requested_url,
+                },
+                &state.url_canonicalizer,
+                &state.recrawl_policy,
+            ),
         )
         .await;
 
-        neo4j_result
-            .or_raise(|| Error::InsertNeo4j)
-            .or_raise(|| HttpError::Internal("".into()))?;
-        pg_result
-            .or_raise(|| Error::InsertPg)
-            .or_raise(|| HttpError::Internal("".into()))?;
+    neo4j_result.or_raise(|| Error::InsertNeo4j)?;
+    let stored = pg_result.or_raise(|| Error::InsertPg)?;
+    if stored {
+        METRICS.record_page_parsed();
     }
 
     Ok(())
@@ -1,20 +1,46 @@
-use std::{fmt, net::IpAddr};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
-use axum::Router;
+use axum::{Router, middleware::from_fn};
 use envconfig::Envconfig;
 use neo4rs::Graph;
 use oxalate_env::load_env_vars;
-use oxalate_init::{init_kafka_producer, init_logger, init_neo4j_pool, init_postgres_pool};
+use oxalate_init::{
+    init_kafka_consumer, init_kafka_producer, init_logger, init_neo4j_pool, init_postgres_pool,
+    init_tracer,
+};
+use oxalate_middleware::{
+    auth_middleware::ApiKeys, logging_middleware::logging_middleware,
+    metrics_middleware::metrics_middleware,
+};
+use oxalate_parsing::{
+    canonicalize_url::UrlCanonicalizer,
+    neo4j_ingestor::{Neo4jIngestConfig, Neo4jIngestor},
+    recrawl::RecrawlPolicy,
+};
 use rdkafka::producer::FutureProducer;
 use sqlx::{Pool, Postgres};
 
 pub mod endpoints;
 
+pub mod proxy_res_consumer;
+use proxy_res_consumer::{ProxyResConsumerConfig, run_proxy_res_consumer};
+
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: Pool<Postgres>,
     pub neo4j_pool: Graph,
     pub kafka_producer_client: Option<FutureProducer>,
+    pub url_canonicalizer: Arc<UrlCanonicalizer>,
+    pub neo4j_ingestor: Arc<Neo4jIngestor>,
+    pub recrawl_policy: Arc<RecrawlPolicy>,
+    pub api_keys: Arc<ApiKeys>,
+    /// pages of one request or batch that are parsed and stored at once
+    pub insert_concurrency: usize,
 }
 
 #[derive(Envconfig)]
@@ -33,6 +59,18 @@ pub struct EnvVars {
     #[envconfig(from = "KAFKA_PARSER_LOGS_TOPIC", default = "parser_logs")]
     pub kafka_parser_logs_topic: String,
 
+    // the harvester publishes proxy outputs here, parsers share the work as one consumer group
+    #[envconfig(from = "KAFKA_PROXY_RES_TOPIC", default = "proxy_res")]
+    pub kafka_proxy_res_topic: String,
+    #[envconfig(from = "KAFKA_PROXY_RES_DLQ_TOPIC", default = "proxy_res_dead_letters")]
+    pub kafka_proxy_res_dlq_topic: String,
+    #[envconfig(from = "KAFKA_PARSER_GROUP_ID", default = "oxalate_parser")]
+    pub kafka_parser_group_id: String,
+    #[envconfig(from = "PROXY_RES_MAX_ATTEMPTS", default = "5")]
+    pub proxy_res_max_attempts: u32,
+    #[envconfig(from = "PROXY_RES_RETRY_BACKOFF_MS", default = "1000")]
+    pub proxy_res_retry_backoff_ms: u64,
+
     // Neo4j
     #[envconfig(from = "NEO4J_AUTH", default = "neo4j/rootrootroot")]
     pub neo4j_auth: String,
@@ -41,6 +79,10 @@ pub struct EnvVars {
     #[envconfig(from = "NEO4J_DNS")]
     pub neo4j_dns: String,
 
+    // tracing, spans go to an otlp/http collector, e.g. http://localhost:4318/v1/traces
+    #[envconfig(from = "OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>, // without it spans are not exported
+
     // Postgres
     #[envconfig(from = "POSTGRES_USER")]
     pub postgres_user: String,
@@ -61,6 +103,43 @@ pub struct EnvVars {
     pub indexer_bind_address: IpAddr,
     #[envconfig(from = "PARSER_PORT", default = "11167")]
     pub indexer_port: u16,
+
+    // comma separated, a trailing * matches every param with that prefix
+    #[envconfig(
+        from = "URL_TRACKING_PARAMS",
+        default = "utm_*,fbclid,gclid,dclid,gbraid,wbraid,msclkid,yclid,mc_cid,mc_eid,_ga,_gl,igshid,ref_src,spm"
+    )]
+    pub url_tracking_params: String,
+    // stores http pages under their https url, only safe when the crawled sites all serve https
+    #[envconfig(from = "URL_FOLD_HTTP_INTO_HTTPS", default = "false")]
+    pub url_fold_http_into_https: bool,
+
+    // keyword graph ingestion, pages are buffered and written in batches
+    #[envconfig(from = "NEO4J_INGEST_WINDOW_SIZE", default = "5")]
+    pub neo4j_ingest_window_size: usize,
+    #[envconfig(from = "NEO4J_INGEST_BATCH_SIZE", default = "64")]
+    pub neo4j_ingest_batch_size: usize,
+    #[envconfig(from = "NEO4J_INGEST_FLUSH_INTERVAL_MS", default = "1000")]
+    pub neo4j_ingest_flush_interval_ms: u64,
+    #[envconfig(from = "NEO4J_INGEST_QUEUE_CAPACITY", default = "1024")]
+    pub neo4j_ingest_queue_capacity: usize,
+    // pages stored at once share a neo4j batch, too many of them flood the databases
+    #[envconfig(from = "PARSER_INSERT_CONCURRENCY", default = "8")]
+    pub parser_insert_concurrency: usize,
+
+    // revisit scheduling, the interval halves when a page changed and grows when it did not
+    #[envconfig(from = "RECRAWL_INITIAL_INTERVAL_HOURS", default = "168")]
+    pub recrawl_initial_interval_hours: u64,
+    #[envconfig(from = "RECRAWL_MIN_INTERVAL_HOURS", default = "6")]
+    pub recrawl_min_interval_hours: u64,
+    #[envconfig(from = "RECRAWL_MAX_INTERVAL_HOURS", default = "2160")]
+    pub recrawl_max_interval_hours: u64,
+    #[envconfig(from = "KEEP_PAGE_HISTORY", default = "false")]
+    pub keep_page_history: bool,
+
+    // keys allowed on /metrics as comma separated name:role:key, shared with the private harvester api
+    #[envconfig(from = "PRIVATE_API_KEYS")]
+    pub private_api_keys: String,
 }
 
 impl fmt::Debug for AppState {
@@ -96,6 +175,9 @@ async fn main() {
         kafka_producer_client.to_owned(),
     )
     .await;
+    // kept for the process lifetime, the servers here have no graceful shutdown to flush on
+    let _tracer_provider = init_tracer("oxalate-parser", env_vars.otlp_endpoint.as_deref())
+        .expect("failed to init the tracer");
 
     let db_pool = init_postgres_pool(
         &env_vars.postgres_user,
@@ -114,15 +196,60 @@ async fn main() {
     )
     .await;
 
+    let neo4j_ingestor = Neo4jIngestor::spawn(
+        neo4j_pool.to_owned(),
+        Neo4jIngestConfig {
+            window_size: env_vars.neo4j_ingest_window_size,
+            batch_size: env_vars.neo4j_ingest_batch_size,
+            flush_interval: Duration::from_millis(env_vars.neo4j_ingest_flush_interval_ms),
+            queue_capacity: env_vars.neo4j_ingest_queue_capacity,
+        },
+    );
+
     let state = AppState {
         db_pool,
         kafka_producer_client,
         neo4j_pool,
+        url_canonicalizer: Arc::new(UrlCanonicalizer::from_config(
+            &env_vars.url_tracking_params,
+            env_vars.url_fold_http_into_https,
+        )),
+        neo4j_ingestor: Arc::new(neo4j_ingestor),
+        recrawl_policy: Arc::new(RecrawlPolicy {
+            initial_interval: Duration::from_secs(env_vars.recrawl_initial_interval_hours * 3600),
+            min_interval: Duration::from_secs(env_vars.recrawl_min_interval_hours * 3600),
+            max_interval: Duration::from_secs(env_vars.recrawl_max_interval_hours * 3600),
+            keep_history: env_vars.keep_page_history,
+        }),
+        api_keys: Arc::new(
+            ApiKeys::from_config(&env_vars.private_api_keys)
+                .expect("refusing to start with a weak PRIVATE_API_KEYS key"),
+        ),
+        insert_concurrency: env_vars.parser_insert_concurrency.max(1),
     };
 
+    if let Some(dns) = env_vars.kafka_dns.as_ref() {
+        let consumer = init_kafka_consumer(
+            dns,
+            env_vars.kafka_port,
+            &env_vars.kafka_parser_group_id,
+            &[&env_vars.kafka_proxy_res_topic],
+        )
+        .await
+        .expect("failed to init kafka consumer");
+        let config = ProxyResConsumerConfig {
+            max_attempts: env_vars.proxy_res_max_attempts,
+            retry_backoff: Duration::from_millis(env_vars.proxy_res_retry_backoff_ms),
+            dead_letter_topic: env_vars.kafka_proxy_res_dlq_topic.to_owned(),
+        };
+        tokio::spawn(run_proxy_res_consumer(state.to_owned(), consumer, config));
+    }
+
     let app = Router::new()
         .merge(endpoints::endpoints(&state))
-        .with_state(state);
+        .with_state(state)
+        .layer(from_fn(metrics_middleware))
+        .layer(from_fn(logging_middleware));
 
     let listener = tokio::net::TcpListener::bind(format!(
         "{}:{}",
@@ -131,5 +258,10 @@ async fn main() {
     .await
     .unwrap();
     log::info!("server listening on {listener:?}");
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
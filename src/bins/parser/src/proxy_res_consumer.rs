@@ -0,0 +1,294 @@
+use std::{collections::HashMap, time::Duration};
+
+use exn::{Result, ResultExt};
+use futures::{StreamExt, TryStreamExt, stream};
+use opentelemetry::{Context, KeyValue, context::FutureExt, trace::SpanKind};
+use oxalate_middleware::{
+    metrics::{METRICS, QueryStore},
+    telemetry::{child_context, context_from_carrier},
+};
+use oxalate_parsing::recrawl::{CrawlOutcome, Validators, queue_url, record_visit};
+use oxalate_schemas::parser::{post_insert_webpage::Page, proxy_res_batch::ProxyResBatch};
+use oxalate_scraper_controller::scraper_controller::{FetchStatus, ProxyRes};
+use rdkafka::{
+    Message,
+    consumer::{CommitMode, Consumer, StreamConsumer},
+    message::{BorrowedMessage, Headers},
+    producer::FutureRecord,
+};
+use tokio::time::sleep;
+use url::Url;
+
+use crate::{AppState, endpoints::post_insert_webpage::insert_webpage};
+
+/// How long a dead letter waits for room in the producer queue before it is given up.
+const KAFKA_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to insert a page of the proxy res batch")]
+    InsertPage,
+
+    #[error("failed to serialize the headers")]
+    Headers,
+
+    #[error("failed to record an unchanged visit")]
+    RecordVisit,
+
+    #[error("failed to queue the target of a redirect")]
+    QueueRedirect,
+}
+
+pub struct ProxyResConsumerConfig {
+    pub max_attempts: u32,
+    /// doubled after every failed attempt
+    pub retry_backoff: Duration,
+    /// batches that failed every attempt end up here, they are only logged without a producer
+    pub dead_letter_topic: String,
+}
+
+/// Consumes the proxy res batches the harvester publishes. An offset is only
+/// committed once its batch is stored or dead lettered, so after a crash the
+/// batch is handled again. That is fine since storing a page is idempotent.
+pub async fn run_proxy_res_consumer(
+    state: AppState,
+    consumer: StreamConsumer,
+    config: ProxyResConsumerConfig,
+) {
+    loop {
+        let msg = match consumer.recv().await {
+            Ok(msg) => msg,
+            Err(err) => {
+                log::error!("failed to receive from the proxy res topic: {err}");
+                sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        handle_message(&state, &msg, &config)
+            .with_context(message_context(&msg))
+            .await;
+
+        if let Err(err) = consumer.commit_message(&msg, CommitMode::Async) {
+            log::error!(
+                "failed to commit offset {} of partition {}: {err}",
+                msg.offset(),
+                msg.partition()
+            );
+        }
+    }
+}
+
+/// A consumer span under the trace the harvester sent along in the message headers.
+fn message_context(msg: &BorrowedMessage<'_>) -> Context {
+    let carrier = msg
+        .headers()
+        .map(|headers| {
+            headers
+                .iter()
+                .filter_map(|e| {
+                    Some((e.key.to_owned(), String::from_utf8(e.value?.to_vec()).ok()?))
+                })
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+
+    child_context(
+        &context_from_carrier(&carrier),
+        format!("process {}", msg.topic()),
+        SpanKind::Consumer,
+        vec![
+            KeyValue::new("messaging.system", "kafka"),
+            KeyValue::new("messaging.destination.name", msg.topic().to_owned()),
+        ],
+    )
+}
+
+async fn handle_message(
+    state: &AppState,
+    msg: &BorrowedMessage<'_>,
+    config: &ProxyResConsumerConfig,
+) {
+    let payload = msg.payload().unwrap_or_default();
+    let batch = match serde_json::from_slice::<ProxyResBatch>(payload) {
+        Ok(batch) => batch,
+        Err(err) => {
+            log::error!(
+                "proxy res message at offset {} is not a batch, dead lettering it: {err}",
+                msg.offset()
+            );
+            dead_letter(state, msg, config).await;
+            return;
+        }
+    };
+
+    let mut backoff = config.retry_backoff;
+    for attempt in 1..=config.max_attempts.max(1) {
+        match insert_batch(state, &batch).await {
+            Ok(pages) => {
+                log::info!(
+                    "stored {pages} pages of proxy res batch {} from {}",
+                    batch.id,
+                    batch.proxy_id
+                );
+                return;
+            }
+            Err(err) => {
+                log::warn!(
+                    "attempt {attempt} of storing proxy res batch {} failed: {err:?}",
+                    batch.id
+                );
+                if attempt < config.max_attempts {
+                    sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                }
+            }
+        }
+    }
+
+    log::error!(
+        "giving up on proxy res batch {} after {} attempts, dead lettering it",
+        batch.id,
+        config.max_attempts
+    );
+    dead_letter(state, msg, config).await;
+}
+
+/// Stores every successful html response of the batch, returns how many pages were stored.
+async fn insert_batch(state: &AppState, batch: &ProxyResBatch) -> Result<usize, Error> {
+    let mut pages = vec![];
+    for output in batch.outputs.iter() {
+        let ProxyRes::HttpRes(http_res) = output;
+        if http_res.fetch_status == FetchStatus::NotModified || http_res.status == 304 {
+            record_not_modified(state, batch, &http_res.url, &http_res.headers).await?;
+            continue;
+        }
+        if let Some(location) = http_res.unfollowed_redirect() {
+            record_redirect(state, batch, &http_res.url, &location).await?;
+            continue;
+        }
+        // error pages, empty bodies and skipped downloads are not worth indexing,
+        // the harvester retries the error pages
+        if !(200..300).contains(&http_res.status) || http_res.body.is_empty() {
+            continue;
+        }
+        if http_res.fetch_status == FetchStatus::Truncated {
+            log::warn!("indexing the truncated body of {}", http_res.url);
+        }
+
+        // redirect targets are indexed under the url that served them
+        let page = Page {
+            url: http_res.final_url().to_owned(),
+            requested_url: Some(http_res.url.to_owned()).filter(|_| !http_res.redirects.is_empty()),
+            body: http_res.body.to_owned(),
+            content_type: http_res.content_type.to_owned(),
+            headers: Some(http_res.headers.to_owned()),
+            proxy_id: batch.proxy_id.to_owned(),
+        };
+        pages.push(page);
+    }
+
+    // a few at once, so their neo4j keywords share a batch instead of each waiting for the flush
+    let stored = pages.len();
+    stream::iter(pages)
+        .map(|page| insert_webpage(state, page))
+        .buffer_unordered(state.insert_concurrency)
+        .try_collect::<Vec<_>>()
+        .await
+        .or_raise(|| Error::InsertPage)?;
+
+    Ok(stored)
+}
+
+/// A conditional GET that came back 304, the page is unchanged so only its schedule moves.
+async fn record_not_modified(
+    state: &AppState,
+    batch: &ProxyResBatch,
+    url: &Url,
+    headers: &HashMap<String, String>,
+) -> Result<(), Error> {
+    let headers = serde_json::to_value(headers).or_raise(|| Error::Headers)?;
+    let url = state
+        .url_canonicalizer
+        .canonicalize(url)
+        .unwrap_or_else(|| url.to_owned());
+
+    METRICS
+        .time_query(
+            QueryStore::Postgres,
+            "record_visit",
+            record_visit(
+                &state.db_pool,
+                &url,
+                &batch.proxy_id,
+                &state.recrawl_policy,
+                &Validators::from_headers(&headers),
+                CrawlOutcome::Unchanged,
+            ),
+        )
+        .await
+        .or_raise(|| Error::RecordVisit)
+}
+
+/// A redirect to another origin, its target goes into the frontier so it is
+/// crawled through robots.txt and the host scheduler like any other url.
+async fn record_redirect(
+    state: &AppState,
+    batch: &ProxyResBatch,
+    url: &Url,
+    location: &Url,
+) -> Result<(), Error> {
+    if let Some(location) = state.url_canonicalizer.canonicalize(location) {
+        METRICS
+            .time_query(
+                QueryStore::Postgres,
+                "queue_url",
+                queue_url(&state.db_pool, &location, &batch.proxy_id),
+            )
+            .await
+            .or_raise(|| Error::QueueRedirect)?;
+    }
+
+    let url = state
+        .url_canonicalizer
+        .canonicalize(url)
+        .unwrap_or_else(|| url.to_owned());
+    METRICS
+        .time_query(
+            QueryStore::Postgres,
+            "record_visit",
+            record_visit(
+                &state.db_pool,
+                &url,
+                &batch.proxy_id,
+                &state.recrawl_policy,
+                &Validators::default(),
+                CrawlOutcome::Unchanged,
+            ),
+        )
+        .await
+        .or_raise(|| Error::RecordVisit)
+}
+
+async fn dead_letter(state: &AppState, msg: &BorrowedMessage<'_>, config: &ProxyResConsumerConfig) {
+    let Some(ref producer) = state.kafka_producer_client else {
+        return;
+    };
+
+    let payload = msg.payload().unwrap_or_default();
+    let key = msg.key().unwrap_or_default();
+    let status = producer
+        .send(
+            FutureRecord::to(&config.dead_letter_topic)
+                .payload(payload)
+                .key(key),
+            KAFKA_QUEUE_TIMEOUT,
+        )
+        .await;
+    if let Err((err, _)) = status {
+        log::error!(
+            "failed to dead letter proxy res message at offset {}: {err}",
+            msg.offset()
+        );
+    }
+}
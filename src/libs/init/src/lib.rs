@@ -4,7 +4,14 @@ use exn::{Result, ResultExt};
 use kafka_writer_rs::KafkaLogWriter;
 use log_json_serializer::parse_log;
 use neo4rs::Graph;
-use rdkafka::{ClientConfig, producer::FutureProducer};
+use opentelemetry::global;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use rdkafka::{
+    ClientConfig,
+    consumer::{Consumer, StreamConsumer},
+    producer::FutureProducer,
+};
 use sqlx::{Pool, Postgres, postgres::PgPoolOptions};
 use tokio::time::sleep;
 
@@ -12,6 +19,15 @@ use tokio::time::sleep;
 pub enum KafkaError {
     #[error("Failet to connect to Kafka to create producer")]
     Connecton,
+
+    #[error("failed to subscribe the kafka consumer to its topics")]
+    Subscribe,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TracerError {
+    #[error("failed to create the otlp span exporter")]
+    Exporter,
 }
 
 pub async fn init_kafka_producer(
@@ -33,6 +49,32 @@ pub async fn init_kafka_producer(
     Ok(client)
 }
 
+/// A consumer in `group_id` that never commits on its own, offsets are committed
+/// after a message was handled so every message is handled at least once.
+pub async fn init_kafka_consumer(
+    kafka_dns: &str,
+    kafka_port: u16,
+    group_id: &str,
+    topics: &[&str],
+) -> Result<StreamConsumer, KafkaError> {
+    let kafka_connect_url = format!("{}:{}", kafka_dns, kafka_port);
+
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", kafka_connect_url)
+        .set("group.id", group_id)
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest")
+        .create()
+        .or_raise(|| KafkaError::Connecton)?;
+    consumer
+        .subscribe(topics)
+        .or_raise(|| KafkaError::Subscribe)?;
+
+    log::info!("kafka consumer of group {group_id} subscribed to {topics:?}");
+
+    Ok(consumer)
+}
+
 pub async fn init_logger(logs_topic: String, producer: Option<FutureProducer>) {
     let fern = fern::Dispatch::new()
         .format(|out, message, record| {
@@ -117,3 +159,33 @@ pub async fn init_neo4j_pool(neo4j_auth: &str, neo4j_dns: &str, neo4j_port: u16)
 
     neo4j_pool
 }
+
+/// Sets up the W3C trace context propagation and, when there is an
+/// `otlp_endpoint`, exports the spans to it over otlp/http. Without one the
+/// spans are not recorded, but an incoming traceparent is still passed on.
+/// The provider has to be shut down on exit to flush the last spans.
+pub fn init_tracer(
+    service_name: &'static str,
+    otlp_endpoint: Option<&str>,
+) -> Result<Option<SdkTracerProvider>, TracerError> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let Some(otlp_endpoint) = otlp_endpoint else {
+        log::info!("no otlp endpoint is set, spans are not exported");
+        return Ok(None);
+    };
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(otlp_endpoint)
+        .build()
+        .or_raise(|| TracerError::Exporter)?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build();
+    global::set_tracer_provider(provider.to_owned());
+
+    log::info!("exporting spans to {otlp_endpoint}");
+    Ok(Some(provider))
+}
@@ -0,0 +1,76 @@
+use axum::{
+    Extension, debug_middleware,
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    logging_middleware::LoggingCTX,
+    metrics::{METRICS, QueryStore},
+};
+
+/// Writes every mutation into `ControlAuditLog` once it is answered, rejected
+/// ones included. The caller comes from the `LoggingCTX`, so this has to run
+/// inside the logging middleware and around the auth middleware.
+#[debug_middleware]
+pub async fn audit_middleware(
+    State(db_pool): State<Pool<Postgres>>,
+    Extension(logging_ctx): Extension<LoggingCTX>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return next.run(request).await;
+    }
+
+    let response = next.run(request).await;
+    let status = response.status().as_u16() as i16;
+
+    let (req_id, method, uri, ip, caller) = logging_ctx.with_mutate(|e| {
+        (
+            e.req_id,
+            e.method.to_string(),
+            e.uri.to_string(),
+            e.ip.ip().to_string(),
+            e.extra_ctx.get("caller").cloned(),
+        )
+    });
+    let field = |key: &str| {
+        caller
+            .as_ref()
+            .and_then(|e| e.get(key))
+            .and_then(|e| e.as_str())
+            .map(|e| e.to_owned())
+    };
+
+    let insert = sqlx::query!(
+        "
+            INSERT INTO ControlAuditLog
+                (req_id, caller, role, method, uri, status, ip)
+            VALUES
+                ($1, $2, $3, $4, $5, $6, $7);
+        ",
+        req_id,
+        field("name"),
+        field("role"),
+        method,
+        uri,
+        status,
+        ip,
+    )
+    .execute(&db_pool);
+    let result = METRICS
+        .time_query(QueryStore::Postgres, "insert_audit_log", insert)
+        .await;
+    if let Err(err) = result {
+        log::error!(ctx:serde = logging_ctx; "failed to write the audit log: {err}");
+    }
+
+    response
+}
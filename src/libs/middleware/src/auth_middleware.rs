@@ -0,0 +1,184 @@
+use std::{fmt, sync::Arc};
+
+use axum::{
+    debug_middleware,
+    extract::{Request, State},
+    http::{Method, header::AUTHORIZATION},
+    middleware::Next,
+    response::Response,
+};
+use exn::Exn;
+use http_error::HttpError;
+use serde::Serialize;
+
+use crate::logging_middleware::LoggingCTX;
+
+pub const API_KEY_HEADER_KEY: &str = "x-api-key";
+/// Keys shorter than this can be guessed.
+const MIN_KEY_BYTES: usize = 32;
+/// The placeholders of deploy/.env.example all start with this.
+const EXAMPLE_KEY_PREFIX: &str = "change-me";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("api key {0} has to be a random value of at least {MIN_KEY_BYTES} bytes")]
+    WeakKey(String),
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// GET, HEAD and OPTIONS only
+    ReadOnly,
+    Admin,
+}
+
+impl Role {
+    fn parse(role: &str) -> Option<Self> {
+        match role.trim().to_ascii_lowercase().as_str() {
+            "read_only" | "readonly" | "read" => Some(Self::ReadOnly),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    fn required_for(method: &Method) -> Self {
+        match *method {
+            Method::GET | Method::HEAD | Method::OPTIONS => Self::ReadOnly,
+            _ => Self::Admin,
+        }
+    }
+}
+
+/// Who made the request, inserted as an extension and into the `LoggingCTX`.
+#[derive(Serialize, Clone, Debug)]
+pub struct Caller {
+    pub name: String,
+    pub role: Role,
+}
+
+struct ApiKey {
+    key: String,
+    caller: Caller,
+}
+
+/// The keys allowed on an api, from a comma separated `name:role:key` list.
+pub struct ApiKeys(Vec<ApiKey>);
+
+impl fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let callers = self.0.iter().map(|e| &e.caller).collect::<Vec<_>>();
+        f.debug_tuple("ApiKeys").field(&callers).finish()
+    }
+}
+
+impl ApiKeys {
+    /// Malformed entries are skipped with an error log, so a typo locks that key
+    /// out instead of the whole api. Short and example keys are refused outright,
+    /// anyone could call the api with those.
+    pub fn from_config(config: &str) -> exn::Result<Self, Error> {
+        let keys = config
+            .split(',')
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+            .filter_map(|entry| {
+                let mut parts = entry.splitn(3, ':');
+                let (Some(name), Some(role), Some(key)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    log::error!("api key entry is not name:role:key, skipping it");
+                    return None;
+                };
+                let Some(role) = Role::parse(role) else {
+                    log::error!("api key {name} has an unknown role {role}, skipping it");
+                    return None;
+                };
+                if key.is_empty() {
+                    log::error!("api key {name} is empty, skipping it");
+                    return None;
+                }
+
+                Some(ApiKey {
+                    key: key.to_owned(),
+                    caller: Caller {
+                        name: name.to_owned(),
+                        role,
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(weak) = keys
+            .iter()
+            .find(|e| e.key.len() < MIN_KEY_BYTES || e.key.starts_with(EXAMPLE_KEY_PREFIX))
+        {
+            return Err(Exn::new(Error::WeakKey(weak.caller.name.clone())));
+        }
+        if keys.is_empty() {
+            log::warn!("no api keys are configured, every request will be rejected");
+        }
+        Ok(Self(keys))
+    }
+
+    fn find(&self, key: &str) -> Option<&Caller> {
+        // every key is compared in full so the timing says nothing about which one matched
+        self.0.iter().fold(None, |found, e| {
+            let matches = constant_time_eq(e.key.as_bytes(), key.as_bytes());
+            found.or(matches.then_some(&e.caller))
+        })
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Takes an api key from `Authorization: Bearer <key>` or `x-api-key`, reads
+/// need a read only key and everything else an admin key.
+#[debug_middleware]
+pub async fn auth_middleware(
+    State(api_keys): State<Arc<ApiKeys>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, HttpError> {
+    let headers = request.headers();
+    let key = headers
+        .get(AUTHORIZATION)
+        .and_then(|e| e.to_str().ok())
+        .and_then(|e| e.strip_prefix("Bearer "))
+        .or_else(|| {
+            headers
+                .get(API_KEY_HEADER_KEY)
+                .and_then(|e| e.to_str().ok())
+        })
+        .map(|e| e.trim());
+
+    let Some(key) = key else {
+        return Err(HttpError::Unauthorized("Missing api key!".into()));
+    };
+    let Some(caller) = api_keys.find(key).cloned() else {
+        return Err(HttpError::Unauthorized("Invalid api key!".into()));
+    };
+
+    let required = Role::required_for(request.method());
+    let ext = request.extensions_mut();
+    if let Some(e) = ext.get_mut::<LoggingCTX>() {
+        e.add_extra("caller", &caller);
+    }
+    if caller.role < required {
+        log::info!(
+            "{} with role {:?} needs {required:?}",
+            caller.name,
+            caller.role
+        );
+        return Err(HttpError::Forbidden(format!(
+            "This needs the {required:?} role!"
+        )));
+    }
+    ext.insert(caller);
+
+    Ok(next.run(request).await)
+}
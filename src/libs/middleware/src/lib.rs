@@ -1 +1,8 @@
 pub mod logging_middleware;
+
+pub mod audit_middleware;
+pub mod auth_middleware;
+
+pub mod metrics;
+pub mod metrics_middleware;
+pub mod telemetry;
@@ -11,9 +11,16 @@ use axum::{
     response::Response,
 };
 use http_error::HttpError;
+use opentelemetry::{
+    KeyValue,
+    context::FutureExt,
+    trace::{SpanKind, Status, TraceContextExt},
+};
 use serde::{Serialize, Serializer};
 use uuid::Uuid;
 
+use crate::telemetry::{child_context, context_from_headers};
+
 #[derive(Clone)]
 pub struct LoggingCTX(Arc<RwLock<LoggingCTXInner>>);
 impl Serialize for LoggingCTX {
@@ -60,6 +67,10 @@ pub struct LoggingCTXInner {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<u16>,
 
+    /// shared by every service the request passes through, unlike `req_id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+
     pub extra_ctx: serde_json::Map<String, serde_json::Value>,
 }
 
@@ -85,6 +96,7 @@ impl LoggingCTX {
             method,
             uri,
             status: None,
+            trace_id: None,
             host,
             ip,
             extra_ctx: serde_json::Map::new(),
@@ -113,12 +125,39 @@ pub async fn logging_middleware(
         host,
         addr,
     );
+
+    // continues the trace of the calling service when it sent a traceparent
+    let (method, path) = logging_ctx.with_mutate(|e| (e.method.to_owned(), e.path.to_owned()));
+    let cx = child_context(
+        &context_from_headers(request.headers()),
+        format!("{method} {path}"),
+        SpanKind::Server,
+        vec![
+            KeyValue::new("http.request.method", method.to_string()),
+            KeyValue::new("http.route", path.to_string()),
+            KeyValue::new("url.path", request.uri().path().to_owned()),
+        ],
+    );
+    let span_context = cx.span().span_context().to_owned();
+    if span_context.is_valid() {
+        logging_ctx.with_mutate(|e| e.trace_id = Some(span_context.trace_id().to_string()));
+    }
+
     request.extensions_mut().insert(logging_ctx.to_owned());
     log::debug!(ctx:serde = logging_ctx; "request start");
 
-    let response = next.run(request).await;
-
-    logging_ctx.with_mutate(|e| e.status = Some(response.status().as_u16()));
+    let response = next.run(request).with_context(cx.to_owned()).await;
+
+    let status = response.status();
+    logging_ctx.with_mutate(|e| e.status = Some(status.as_u16()));
+    let span = cx.span();
+    span.set_attribute(KeyValue::new(
+        "http.response.status_code",
+        status.as_u16() as i64,
+    ));
+    if status.is_server_error() {
+        span.set_status(Status::error(status.to_string()));
+    }
     log::debug!(ctx:serde = logging_ctx; "request end");
 
     Ok(response)
@@ -0,0 +1,213 @@
+use std::{
+    future::Future,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use http_error::HttpError;
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+use crate::telemetry::in_query_span;
+
+/// The metrics of the service, every service has its own process so one
+/// registry per process is all there is. Exposed on `/metrics` by `get_metrics`.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Latency buckets in seconds, from a cache hit up to a slow external engine.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+pub struct Metrics {
+    registry: Registry,
+    /// by method, matched path and status
+    pub http_request_duration: HistogramVec,
+    /// by store and query name
+    pub query_duration: HistogramVec,
+    /// by event, see `TaskEvent`
+    pub tasks: IntCounterVec,
+    /// `rate()` of this is the pages parsed per second
+    pub pages_parsed: IntCounter,
+    /// by engine and outcome, see `SearchOutcome`
+    pub search_engine_requests: IntCounterVec,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum QueryStore {
+    Postgres,
+    Neo4j,
+}
+
+impl QueryStore {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Postgres => "postgres",
+            Self::Neo4j => "neo4j",
+        }
+    }
+
+    /// the `db.system.name` of the opentelemetry semantic conventions
+    pub(crate) fn db_system(&self) -> &'static str {
+        match self {
+            Self::Postgres => "postgresql",
+            Self::Neo4j => "neo4j",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum TaskEvent {
+    HandedOut,
+    Completed,
+    /// the lease ran out before the proxy returned the outputs
+    Dead,
+}
+
+impl TaskEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::HandedOut => "handed_out",
+            Self::Completed => "completed",
+            Self::Dead => "dead",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SearchOutcome {
+    Success,
+    Failure,
+    Timeout,
+    /// the circuit breaker of the engine is open
+    Skipped,
+}
+
+impl SearchOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::Timeout => "timeout",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("oxalate".into()), None)
+            .expect("the metrics prefix is valid");
+
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Latency of the http requests per endpoint",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["method", "path", "status"],
+        )
+        .expect("the http request duration metric is valid");
+        let query_duration = HistogramVec::new(
+            HistogramOpts::new("query_duration_seconds", "Latency of the db queries")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["store", "query"],
+        )
+        .expect("the query duration metric is valid");
+        let tasks = IntCounterVec::new(
+            Opts::new("tasks_total", "Proxy tasks handed out, completed and dead"),
+            &["event"],
+        )
+        .expect("the tasks metric is valid");
+        let pages_parsed = IntCounter::new("pages_parsed_total", "Pages parsed and stored")
+            .expect("the pages parsed metric is valid");
+        let search_engine_requests = IntCounterVec::new(
+            Opts::new(
+                "search_engine_requests_total",
+                "Searches per engine and their outcome",
+            ),
+            &["engine", "outcome"],
+        )
+        .expect("the search engine requests metric is valid");
+
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .expect("the http request duration metric is registered once");
+        registry
+            .register(Box::new(query_duration.clone()))
+            .expect("the query duration metric is registered once");
+        registry
+            .register(Box::new(tasks.clone()))
+            .expect("the tasks metric is registered once");
+        registry
+            .register(Box::new(pages_parsed.clone()))
+            .expect("the pages parsed metric is registered once");
+        registry
+            .register(Box::new(search_engine_requests.clone()))
+            .expect("the search engine requests metric is registered once");
+
+        Self {
+            registry,
+            http_request_duration,
+            query_duration,
+            tasks,
+            pages_parsed,
+            search_engine_requests,
+        }
+    }
+
+    pub fn record_tasks(&self, event: TaskEvent, count: u64) {
+        self.tasks
+            .with_label_values(&[event.as_str()])
+            .inc_by(count);
+    }
+
+    pub fn record_page_parsed(&self) {
+        self.pages_parsed.inc();
+    }
+
+    pub fn record_search(&self, engine: &str, outcome: SearchOutcome) {
+        self.search_engine_requests
+            .with_label_values(&[engine, outcome.as_str()])
+            .inc();
+    }
+
+    /// Awaits `fut` in a query span and records how long it took under `query`,
+    /// failed queries included.
+    pub async fn time_query<F: Future>(&self, store: QueryStore, query: &str, fut: F) -> F::Output {
+        let start = Instant::now();
+        let res = in_query_span(store, query, fut).await;
+        self.observe_query(store, query, start.elapsed());
+        res
+    }
+
+    pub fn observe_query(&self, store: QueryStore, query: &str, elapsed: Duration) {
+        self.query_duration
+            .with_label_values(&[store.as_str(), query])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// The registry in the prometheus text format.
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        TextEncoder::new().encode_to_string(&self.registry.gather())
+    }
+}
+
+pub async fn get_metrics() -> Result<Response, HttpError> {
+    let body = METRICS.encode().map_err(|err| {
+        log::error!("failed to encode the metrics: {err}");
+        HttpError::Internal("".into())
+    })?;
+
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        body,
+    )
+        .into_response())
+}
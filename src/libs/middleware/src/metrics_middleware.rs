@@ -0,0 +1,36 @@
+use std::time::Instant;
+
+use axum::{
+    debug_middleware,
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::metrics::METRICS;
+
+/// Records the latency of every request per matched path. Unmatched requests
+/// share one label so random paths can't blow up the series count.
+#[debug_middleware]
+pub async fn metrics_middleware(
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_owned();
+    let path = matched_path
+        .as_ref()
+        .map(|e| e.as_str())
+        .unwrap_or("unmatched")
+        .to_owned();
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+
+    METRICS
+        .http_request_duration
+        .with_label_values(&[method.as_str(), &path, response.status().as_str()])
+        .observe(start.elapsed().as_secs_f64());
+
+    response
+}
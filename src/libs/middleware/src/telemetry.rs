@@ -0,0 +1,92 @@
+use std::{borrow::Cow, collections::HashMap, future::Future};
+
+use axum::http::HeaderMap;
+use opentelemetry::{
+    Context, KeyValue,
+    context::FutureExt,
+    global,
+    trace::{Link, SpanContext, SpanKind, TraceContextExt, Tracer},
+};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+
+use crate::metrics::QueryStore;
+
+pub const TRACER_NAME: &str = "oxalate";
+
+/// The `traceparent` of the current span as headers, for calls to the other services.
+pub fn trace_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&Context::current(), &mut HeaderInjector(&mut headers))
+    });
+    headers
+}
+
+pub fn context_from_headers(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// The `traceparent` of the current span as a plain map, for kafka message headers.
+pub fn trace_carrier() -> HashMap<String, String> {
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&Context::current(), &mut carrier)
+    });
+    carrier
+}
+
+pub fn context_from_carrier(carrier: &HashMap<String, String>) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(carrier))
+}
+
+/// Starts a span under `parent` and returns the context holding it, the span
+/// ends once the last clone of the context is dropped.
+pub fn child_context(
+    parent: &Context,
+    name: impl Into<Cow<'static, str>>,
+    kind: SpanKind,
+    attributes: Vec<KeyValue>,
+) -> Context {
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(name)
+        .with_kind(kind)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, parent);
+    parent.with_span(span)
+}
+
+/// Starts a new trace linked to the spans of `links`, for work batched up from
+/// many requests that has no single parent.
+pub fn linked_context(
+    name: impl Into<Cow<'static, str>>,
+    kind: SpanKind,
+    links: impl IntoIterator<Item = SpanContext>,
+) -> Context {
+    let links = links
+        .into_iter()
+        .filter(|e| e.is_valid())
+        .map(Link::with_context)
+        .collect::<Vec<_>>();
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(name)
+        .with_kind(kind)
+        .with_links(links)
+        .start_with_context(&tracer, &Context::new());
+    Context::new().with_span(span)
+}
+
+/// Runs a db call in a client span under the current one.
+pub async fn in_query_span<F: Future>(store: QueryStore, query: &str, fut: F) -> F::Output {
+    let cx = child_context(
+        &Context::current(),
+        format!("{} {query}", store.db_system()),
+        SpanKind::Client,
+        vec![
+            KeyValue::new("db.system.name", store.db_system()),
+            KeyValue::new("db.operation.name", query.to_owned()),
+        ],
+    );
+    fut.with_context(cx).await
+}
@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use url::Url;
+
+/// Query params stripped by default, entries ending in `*` match by prefix.
+pub const DEFAULT_TRACKING_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "mc_cid",
+    "mc_eid", "_ga", "_gl", "igshid", "ref_src", "spm",
+];
+
+const INDEX_FILES: &[&str] = &[
+    "index.html",
+    "index.htm",
+    "index.php",
+    "default.html",
+    "default.htm",
+    "default.aspx",
+];
+
+/// Maps every url that points at the same page onto one canonical form so
+/// `Webpages` and `Urls` don't fill up with duplicates.
+#[derive(Debug, Clone)]
+pub struct UrlCanonicalizer {
+    tracking_params: HashSet<String>,
+    tracking_prefixes: Vec<String>,
+    /// maps http urls on default ports onto https, only for crawls of sites
+    /// that all serve https
+    fold_http_into_https: bool,
+}
+
+impl UrlCanonicalizer {
+    pub fn new<S: AsRef<str>>(tracking_params: impl IntoIterator<Item = S>) -> Self {
+        let mut exact = HashSet::new();
+        let mut prefixes = vec![];
+        for param in tracking_params {
+            let param = param.as_ref().trim().to_lowercase();
+            if param.is_empty() {
+                continue;
+            }
+            match param.strip_suffix('*') {
+                Some(prefix) => prefixes.push(prefix.to_owned()),
+                None => {
+                    exact.insert(param);
+                }
+            }
+        }
+
+        Self {
+            tracking_params: exact,
+            tracking_prefixes: prefixes,
+            fold_http_into_https: false,
+        }
+    }
+
+    /// Parses a comma separated list like `utm_*,fbclid,gclid`.
+    pub fn from_config(tracking_params: &str, fold_http_into_https: bool) -> Self {
+        Self {
+            fold_http_into_https,
+            ..Self::new(tracking_params.split(','))
+        }
+    }
+
+    /// Returns `None` for everything that is not http(s), like `mailto:` or `javascript:`.
+    pub fn canonicalize(&self, url: &Url) -> Option<Url> {
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            return None;
+        }
+
+        // by default the scheme is kept, plenty of sites don't serve the same page on
+        // both. http then only turns into https through a canonical link, see
+        // `canonical_page_url`
+        let mut url = url.to_owned();
+        if self.fold_http_into_https && url.scheme() == "http" && url.port().is_none() {
+            url.set_scheme("https").ok()?;
+        }
+        url.set_username("").ok()?;
+        url.set_password(None).ok()?;
+        url.set_fragment(None);
+
+        if let Some(host) = url.host_str()
+            && host.ends_with('.')
+        {
+            let host = host.trim_end_matches('.').to_owned();
+            url.set_host(Some(&host)).ok()?;
+        }
+
+        let path = normalize_path(url.path());
+        url.set_path(&path);
+
+        let mut query = url
+            .query_pairs()
+            .filter(|(key, _)| !self.is_tracking_param(key))
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect::<Vec<_>>();
+        query.sort();
+
+        if query.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(query);
+        }
+
+        Some(url)
+    }
+
+    /// The url a page is stored under. A `<link rel=canonical>` is honored as long
+    /// as it stays on the same host, cross host canonicals are too easy to abuse.
+    /// That is also how an http page ends up under its https url.
+    pub fn canonical_page_url(&self, url: &Url, canonical_link: Option<&Url>) -> Url {
+        let page_url = self.canonicalize(url).unwrap_or_else(|| url.to_owned());
+
+        let Some(canonical_url) = canonical_link.and_then(|e| self.canonicalize(e)) else {
+            return page_url;
+        };
+
+        if canonical_url.host_str() == page_url.host_str() {
+            canonical_url
+        } else {
+            page_url
+        }
+    }
+
+    fn is_tracking_param(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.tracking_params.contains(&key)
+            || self
+                .tracking_prefixes
+                .iter()
+                .any(|prefix| key.starts_with(prefix))
+    }
+}
+
+impl Default for UrlCanonicalizer {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRACKING_PARAMS.iter())
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let mut segments = path
+        .split('/')
+        .filter(|e| !e.is_empty())
+        .map(normalize_percent_encoding)
+        .collect::<Vec<_>>();
+
+    if segments
+        .last()
+        .is_some_and(|last| INDEX_FILES.contains(&last.to_lowercase().as_str()))
+    {
+        segments.pop();
+    }
+
+    format!("/{}", segments.join("/"))
+}
+
+/// Decodes escaped unreserved chars and uppercases the hex of every other escape.
+fn normalize_percent_encoding(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = String::with_capacity(segment.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2]))
+        {
+            let decoded = hi * 16 + lo;
+            if decoded.is_ascii_alphanumeric() || b"-._~".contains(&decoded) {
+                out.push(decoded as char);
+            } else {
+                out.push('%');
+                out.push(bytes[i + 1].to_ascii_uppercase() as char);
+                out.push(bytes[i + 2].to_ascii_uppercase() as char);
+            }
+            i += 3;
+            continue;
+        }
+
+        out.push(bytes[i] as char);
+        i += 1;
+    }
+
+    out
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|e| e as u8)
+}
@@ -0,0 +1,84 @@
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252};
+
+/// How far into the body a `<meta charset>` is looked for, same as browsers.
+const META_PRESCAN_BYTES: usize = 1024;
+
+/// Whether the body is worth decoding into text, bodies without a content type are sniffed.
+pub fn is_textual(content_type: Option<&str>) -> bool {
+    let Some(content_type) = content_type else {
+        return true;
+    };
+    let mime = mime_type(content_type);
+
+    mime.starts_with("text/") || mime.ends_with("+xml") || mime.ends_with("/xml")
+}
+
+/// Decodes a raw body into text. The encoding comes from a byte order mark,
+/// the charset of the content type, a `<meta charset>` in the first kilobyte,
+/// or else valid utf-8 is taken as utf-8 and everything else as windows-1252.
+pub fn decode_body(body: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(header_charset)
+        .or_else(|| meta_charset(body))
+        .unwrap_or_else(|| sniff_encoding(body));
+
+    // decode() lets a byte order mark override the encoding
+    let (text, _, _) = encoding.decode(body);
+    text.into_owned()
+}
+
+fn mime_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn header_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, val) = param.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        Encoding::for_label(val.trim().trim_matches(['"', '\'']).as_bytes())
+    })
+}
+
+/// Finds `<meta charset="..">` and `<meta http-equiv="content-type" content="..; charset=..">`.
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = &body[..body.len().min(META_PRESCAN_BYTES)];
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+
+    let encoding = head.split("<meta").skip(1).find_map(|tag| {
+        let tag = tag.split('>').next().unwrap_or_default();
+        let (_, rest) = tag.split_once("charset")?;
+        let label = rest
+            .trim_start()
+            .strip_prefix('=')?
+            .trim_start()
+            .trim_start_matches(['"', '\''])
+            .split(|e: char| e == '"' || e == '\'' || e == ';' || e.is_whitespace() || e == '/')
+            .next()?;
+        Encoding::for_label(label.as_bytes())
+    })?;
+
+    // a page that could be read as ascii to find this is not utf-16
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        return Some(UTF_8);
+    }
+    Some(encoding)
+}
+
+fn sniff_encoding(body: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return encoding;
+    }
+    match std::str::from_utf8(body) {
+        Ok(_) => UTF_8,
+        // only cut off mid character, a truncated body is still utf-8
+        Err(err) if err.error_len().is_none() => UTF_8,
+        Err(_) => WINDOWS_1252,
+    }
+}
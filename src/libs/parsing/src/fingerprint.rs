@@ -0,0 +1,55 @@
+use oxalate_scraper_controller::simhash::simhash;
+use sha2::{Digest, Sha256};
+
+/// Pages whose simhashes differ in at most this many bits are near-duplicates.
+pub const MAX_SIMHASH_DISTANCE: u32 = 3;
+/// With 4 bands of 16 bits two simhashes within a distance of 3 share at
+/// least one band exactly, so candidates can be looked up with an index.
+const SIMHASH_BANDS: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// sha256 of the whitespace and case normalized text, equal for exact copies
+    pub content_hash: String,
+    pub simhash: u64,
+}
+
+impl Fingerprint {
+    /// `keywords` are the tokenized words of the page, they feed the simhash so
+    /// boilerplate differences in stop words and inflections do not count.
+    pub fn new(text: &str, keywords: &[String]) -> Self {
+        Self {
+            content_hash: content_hash(text),
+            simhash: simhash(keywords),
+        }
+    }
+
+    /// The simhash split into bands, each tagged with its position so the
+    /// bands of different positions never collide.
+    pub fn simhash_bands(&self) -> Vec<i32> {
+        simhash_bands(self.simhash)
+    }
+}
+
+pub fn content_hash(text: &str) -> String {
+    let normalized = text
+        .split_whitespace()
+        .map(|e| e.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Sha256::digest(normalized.as_bytes())
+        .iter()
+        .map(|e| format!("{e:02x}"))
+        .collect()
+}
+
+pub fn simhash_bands(simhash: u64) -> Vec<i32> {
+    let band_bits = 64 / SIMHASH_BANDS;
+    (0..SIMHASH_BANDS)
+        .map(|band| {
+            let value = (simhash >> (band * band_bits)) & ((1 << band_bits) - 1);
+            ((band << band_bits) as u64 | value) as i32
+        })
+        .collect()
+}
@@ -1,18 +1,47 @@
-use std::collections::HashSet;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use url::Url;
 
-pub mod save_into_neo4j;
+pub mod neo4j_ingestor;
 
+pub mod canonicalize_url;
 pub mod compress_html;
+pub mod decode_body;
+pub mod fingerprint;
 pub mod parse_html;
+pub mod recrawl;
 pub mod save_meta_webpage_into_postgres;
 pub mod save_parsed_webpage_into_postgres;
-pub mod split_into_words;
+pub mod snippet;
+pub mod stop_words;
+pub mod tokenizer;
 
 pub struct ParsedHtml {
     pub keywords: Vec<String>,
+    /// the readable text of the page, kept for building search snippets
+    pub text: String,
     pub title: String,
     pub urls: HashSet<Url>,
+    pub canonical_url: Option<Url>,
+    pub metadata: PageMetadata,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct PageMetadata {
+    /// meta description, falls back to the og/twitter description
+    pub description: String,
+    pub lang: Option<String>,
+    /// og:* and twitter:* meta tags keyed by their property/name
+    pub social: HashMap<String, String>,
+    pub headings: Vec<Heading>,
+    pub json_ld: Vec<Value>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
 }
 
 // pub async fn save_http_https_output<LoggingCTX: Serialize>(
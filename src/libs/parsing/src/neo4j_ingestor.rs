@@ -0,0 +1,505 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use exn::{Exn, Result, ResultExt};
+use itertools::Itertools;
+use neo4rs::{Graph, query};
+use opentelemetry::{
+    Context,
+    context::FutureExt,
+    trace::{SpanContext, SpanKind, TraceContextExt},
+};
+use oxalate_middleware::{
+    metrics::{METRICS, QueryStore},
+    telemetry::linked_context,
+};
+use tokio::sync::{mpsc, oneshot};
+use url::Url;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("the neo4j ingest queue is closed")]
+    QueueClosed,
+
+    #[error("the batch of the page failed to be written into neo4j")]
+    WriteBatch,
+
+    #[error("failed to load the previous keywords of the pages from neo4j")]
+    LoadPrevious,
+
+    #[error("failed to start the neo4j transaction")]
+    StartTxn,
+
+    #[error("failed to run the queries in the neo4j transaction")]
+    RunQueries,
+
+    #[error("failed to commit the neo4j transaction")]
+    Commit,
+}
+
+pub struct Neo4jIngestConfig {
+    /// words closer than this are RELATED
+    pub window_size: usize,
+    /// max pages written in one transaction
+    pub batch_size: usize,
+    /// how long a batch waits to fill up once its first page arrived
+    pub flush_interval: Duration,
+    /// pages that can wait for the writer before `ingest` blocks
+    pub queue_capacity: usize,
+}
+
+struct IngestPage {
+    url: String,
+    keywords: Vec<String>,
+    /// of the request that queued the page, the batch span links to it
+    span_context: SpanContext,
+    /// told whether the batch holding the page was written
+    done: oneshot::Sender<bool>,
+}
+
+/// What one page adds to the graph. The keywords and window size it came from
+/// are stored on the `Website` node, so a re-ingest can derive it again and
+/// subtract it instead of counting the page twice.
+#[derive(Default)]
+struct Contribution {
+    words: HashMap<String, i64>,
+    pairs: HashMap<(String, String), i64>,
+}
+
+/// What a page added the last time it was ingested.
+enum Previous {
+    Snapshot(Contribution),
+    /// ingested before snapshots were stored, every keyword added one to both
+    /// the usage of its word and the weight of the CONTAINS edge so the words
+    /// come from those edges. The pairs can't be recovered, they are taken to
+    /// be unchanged. Known limitation: RELATED weights such a page counted
+    /// twice stay too high, only rebuilding the graph from scratch fixes them.
+    Legacy(HashMap<String, i64>),
+}
+
+#[derive(Default)]
+struct IngestMetrics {
+    pages: AtomicU64,
+    batches: AtomicU64,
+    failed_batches: AtomicU64,
+    words: AtomicU64,
+    pairs: AtomicU64,
+    busy_micros: AtomicU64,
+    last_batch_micros: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IngestMetricsSnapshot {
+    pub pages_ingested: u64,
+    pub batches: u64,
+    pub failed_batches: u64,
+    /// changed word usages and CONTAINS edges
+    pub words_written: u64,
+    /// changed RELATED edges
+    pub pairs_written: u64,
+    /// pages per second while the writer was busy
+    pub pages_per_sec: f64,
+    pub last_batch_ms: u64,
+    pub queued_pages: usize,
+}
+
+/// Buffers pages and writes them into neo4j in batches from one background
+/// task. Having a single writer keeps the diffing of a page against its
+/// previous keywords free of races.
+pub struct Neo4jIngestor {
+    sender: mpsc::Sender<IngestPage>,
+    metrics: Arc<IngestMetrics>,
+}
+
+impl Neo4jIngestor {
+    pub fn spawn(neo4j_pool: Graph, config: Neo4jIngestConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        let metrics = Arc::new(IngestMetrics::default());
+
+        tokio::spawn(run_writer(neo4j_pool, config, receiver, metrics.to_owned()));
+
+        Self { sender, metrics }
+    }
+
+    /// Queues the page and waits until the batch holding it is written.
+    pub async fn ingest(&self, url: &Url, keywords: Vec<String>) -> Result<(), Error> {
+        let (done, written) = oneshot::channel();
+        let page = IngestPage {
+            url: url.to_string(),
+            keywords,
+            span_context: Context::current().span().span_context().to_owned(),
+            done,
+        };
+        if self.sender.send(page).await.is_err() {
+            return Err(Exn::new(Error::QueueClosed));
+        }
+
+        match written.await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(Exn::new(Error::WriteBatch)),
+            // the writer stopped before the batch was written
+            Err(_) => Err(Exn::new(Error::QueueClosed)),
+        }
+    }
+
+    pub fn metrics(&self) -> IngestMetricsSnapshot {
+        let pages_ingested = self.metrics.pages.load(Ordering::Relaxed);
+        let busy_secs = self.metrics.busy_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let pages_per_sec = if busy_secs > 0.0 {
+            pages_ingested as f64 / busy_secs
+        } else {
+            0.0
+        };
+
+        IngestMetricsSnapshot {
+            pages_ingested,
+            batches: self.metrics.batches.load(Ordering::Relaxed),
+            failed_batches: self.metrics.failed_batches.load(Ordering::Relaxed),
+            words_written: self.metrics.words.load(Ordering::Relaxed),
+            pairs_written: self.metrics.pairs.load(Ordering::Relaxed),
+            pages_per_sec,
+            last_batch_ms: self.metrics.last_batch_micros.load(Ordering::Relaxed) / 1_000,
+            queued_pages: self.sender.max_capacity() - self.sender.capacity(),
+        }
+    }
+}
+
+async fn run_writer(
+    neo4j_pool: Graph,
+    config: Neo4jIngestConfig,
+    mut receiver: mpsc::Receiver<IngestPage>,
+    metrics: Arc<IngestMetrics>,
+) {
+    let batch_size = config.batch_size.max(1);
+    let mut buffer = Vec::with_capacity(batch_size);
+
+    loop {
+        if receiver.recv_many(&mut buffer, batch_size).await == 0 {
+            log::info!("neo4j ingest queue closed, stopping the writer");
+            break;
+        }
+
+        let deadline = tokio::time::Instant::now() + config.flush_interval;
+        while buffer.len() < batch_size {
+            let missing = batch_size - buffer.len();
+            match tokio::time::timeout_at(deadline, receiver.recv_many(&mut buffer, missing)).await
+            {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+
+        let pages = buffer.len();
+        // a batch holds pages of many traces, so it gets its own linked to all of them
+        let cx = linked_context(
+            "neo4j ingest_batch",
+            SpanKind::Client,
+            buffer.iter().map(|e| e.span_context.to_owned()),
+        );
+        let (pages_keywords, acks): (Vec<_>, Vec<_>) = buffer
+            .drain(..)
+            .map(|e| ((e.url, e.keywords), e.done))
+            .unzip();
+
+        let start = Instant::now();
+        let res = write_batch(&neo4j_pool, pages_keywords, config.window_size)
+            .with_context(cx)
+            .await;
+        let elapsed = start.elapsed();
+
+        // the callers may have given up waiting, that is fine
+        for ack in acks {
+            let _ = ack.send(res.is_ok());
+        }
+
+        METRICS.observe_query(QueryStore::Neo4j, "ingest_batch", elapsed);
+        metrics
+            .busy_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        metrics
+            .last_batch_micros
+            .store(elapsed.as_micros() as u64, Ordering::Relaxed);
+        metrics.batches.fetch_add(1, Ordering::Relaxed);
+
+        match res {
+            Ok((words, pairs)) => {
+                metrics.pages.fetch_add(pages as u64, Ordering::Relaxed);
+                metrics.words.fetch_add(words as u64, Ordering::Relaxed);
+                metrics.pairs.fetch_add(pairs as u64, Ordering::Relaxed);
+                log::info!(
+                    "ingested {pages} pages into neo4j in {elapsed:?}, {words} words and {pairs} pairs changed, {:.1} pages/s",
+                    pages as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+                );
+            }
+            Err(err) => {
+                metrics.failed_batches.fetch_add(1, Ordering::Relaxed);
+                log::error!("failed to ingest {pages} pages into neo4j: {err:?}");
+            }
+        }
+    }
+}
+
+fn contribution(keywords: &[String], window_size: usize) -> Contribution {
+    let mut contribution = Contribution::default();
+    for word in keywords.iter() {
+        *contribution.words.entry(word.to_owned()).or_default() += 1;
+    }
+
+    for window in keywords.windows(window_size.max(2)) {
+        for (word_1, word_2) in window.iter().tuple_combinations() {
+            // a repeated word is no relation
+            if word_1 == word_2 {
+                continue;
+            }
+            let (first, second) = if word_1 < word_2 {
+                (word_1, word_2)
+            } else {
+                (word_2, word_1)
+            };
+            *contribution
+                .pairs
+                .entry((first.to_owned(), second.to_owned()))
+                .or_default() += 1;
+        }
+    }
+
+    contribution
+}
+
+/// Loads what the `Website` nodes of `urls` contributed so far, pages that
+/// were never ingested have nothing.
+async fn load_previous(
+    neo4j_pool: &Graph,
+    urls: &[String],
+) -> Result<HashMap<String, Previous>, Error> {
+    let mut rows = neo4j_pool
+        .execute(
+            query(
+                "
+                UNWIND $urls AS url
+                MATCH (site:Website {url: url})
+                OPTIONAL MATCH (site)-[r:CONTAINS]->(w:Word)
+                  WHERE site.snapshot_keywords IS NULL
+                WITH site, collect(w.text) AS contains_texts, collect(r.weight) AS contains_weights
+                RETURN
+                    site.url AS url,
+                    site.snapshot_keywords IS NOT NULL AS has_snapshot,
+                    contains_texts,
+                    contains_weights,
+                    coalesce(site.snapshot_keywords, []) AS snapshot_keywords,
+                    coalesce(site.snapshot_window, 0) AS snapshot_window
+                ",
+            )
+            .param("urls", urls.to_owned()),
+        )
+        .await
+        .or_raise(|| Error::LoadPrevious)?;
+
+    let mut previous = HashMap::new();
+    while let Some(row) = rows.next().await.or_raise(|| Error::LoadPrevious)? {
+        let url: String = row.get("url").or_raise(|| Error::LoadPrevious)?;
+        let has_snapshot: bool = row.get("has_snapshot").or_raise(|| Error::LoadPrevious)?;
+        if !has_snapshot {
+            let contains_texts: Vec<String> = row.get("contains_texts").unwrap_or_default();
+            let contains_weights: Vec<i64> = row.get("contains_weights").unwrap_or_default();
+            let words = contains_texts.into_iter().zip(contains_weights).collect();
+            previous.insert(url, Previous::Legacy(words));
+            continue;
+        }
+
+        let keywords: Vec<String> = row.get("snapshot_keywords").unwrap_or_default();
+        let window_size: i64 = row.get("snapshot_window").unwrap_or_default();
+        // derived with the window it was written with, the config may have changed since
+        let contribution = contribution(&keywords, window_size.max(0) as usize);
+        previous.insert(url, Previous::Snapshot(contribution));
+    }
+
+    Ok(previous)
+}
+
+/// Writes a batch in one transaction, returns how many word and pair counts changed.
+async fn write_batch(
+    neo4j_pool: &Graph,
+    pages: Vec<(String, Vec<String>)>,
+    window_size: usize,
+) -> Result<(usize, usize), Error> {
+    // the last version of a page wins when it was queued more than once
+    let pages = pages.into_iter().collect::<HashMap<_, _>>();
+    let urls = pages.keys().cloned().collect::<Vec<_>>();
+    let mut previous = load_previous(neo4j_pool, &urls).await?;
+
+    let mut word_deltas: HashMap<String, i64> = HashMap::new();
+    let mut pair_deltas: HashMap<(String, String), i64> = HashMap::new();
+    let (mut contains_urls, mut contains_texts, mut contains_weights) = (vec![], vec![], vec![]);
+    let (mut removed_urls, mut removed_texts) = (vec![], vec![]);
+    let (mut snapshot_urls, mut snapshot_keywords) = (vec![], vec![]);
+
+    for (url, keywords) in pages.into_iter() {
+        let new = contribution(&keywords, window_size);
+        let old = match previous.remove(&url) {
+            Some(Previous::Snapshot(old)) => old,
+            Some(Previous::Legacy(words)) => Contribution {
+                words,
+                pairs: new.pairs.to_owned(),
+            },
+            None => Contribution::default(),
+        };
+
+        for (word, count) in new.words.iter() {
+            let delta = count - old.words.get(word).copied().unwrap_or_default();
+            if delta != 0 {
+                *word_deltas.entry(word.to_owned()).or_default() += delta;
+                contains_urls.push(url.to_owned());
+                contains_texts.push(word.to_owned());
+                contains_weights.push(*count);
+            }
+        }
+        for (word, count) in old.words.iter() {
+            if !new.words.contains_key(word) {
+                *word_deltas.entry(word.to_owned()).or_default() -= count;
+                removed_urls.push(url.to_owned());
+                removed_texts.push(word.to_owned());
+            }
+        }
+
+        let pairs = new
+            .pairs
+            .keys()
+            .chain(old.pairs.keys())
+            .collect::<HashSet<_>>();
+        for pair in pairs {
+            let delta = new.pairs.get(pair).copied().unwrap_or_default()
+                - old.pairs.get(pair).copied().unwrap_or_default();
+            if delta != 0 {
+                *pair_deltas.entry(pair.to_owned()).or_default() += delta;
+            }
+        }
+
+        snapshot_urls.push(url);
+        snapshot_keywords.push(keywords);
+    }
+
+    word_deltas.retain(|_, e| *e != 0);
+    pair_deltas.retain(|_, e| *e != 0);
+    let changed = (word_deltas.len(), pair_deltas.len());
+
+    let (word_texts, word_delta_values): (Vec<_>, Vec<_>) = word_deltas.into_iter().unzip();
+    // only words that lost usage can drop to zero
+    let shrunk_words = word_texts
+        .iter()
+        .zip(word_delta_values.iter())
+        .filter(|(_, delta)| **delta < 0)
+        .map(|(text, _)| text.to_owned())
+        .collect::<Vec<_>>();
+    let (mut firsts, mut seconds, mut pair_delta_values) = (vec![], vec![], vec![]);
+    for ((first, second), delta) in pair_deltas.into_iter() {
+        firsts.push(first);
+        seconds.push(second);
+        pair_delta_values.push(delta);
+    }
+
+    let mut queries = vec![];
+    if !word_texts.is_empty() {
+        queries.push(
+            query(
+                "
+                UNWIND range(0, size($texts) - 1) AS i
+                MERGE (w:Word {text: $texts[i]})
+                  ON CREATE SET w.usage = 0
+                SET w.usage = w.usage + $deltas[i]
+                ",
+            )
+            .param("texts", word_texts)
+            .param("deltas", word_delta_values),
+        );
+    }
+    if !contains_urls.is_empty() {
+        queries.push(
+            query(
+                "
+                UNWIND range(0, size($urls) - 1) AS i
+                MERGE (site:Website {url: $urls[i]})
+                MERGE (w:Word {text: $texts[i]})
+                MERGE (site)-[r:CONTAINS]->(w)
+                SET r.weight = $weights[i]
+                ",
+            )
+            .param("urls", contains_urls)
+            .param("texts", contains_texts)
+            .param("weights", contains_weights),
+        );
+    }
+    if !removed_urls.is_empty() {
+        queries.push(
+            query(
+                "
+                UNWIND range(0, size($urls) - 1) AS i
+                MATCH (:Website {url: $urls[i]})-[r:CONTAINS]->(:Word {text: $texts[i]})
+                DELETE r
+                ",
+            )
+            .param("urls", removed_urls)
+            .param("texts", removed_texts),
+        );
+    }
+    if !firsts.is_empty() {
+        queries.push(
+            query(
+                "
+                UNWIND range(0, size($firsts) - 1) AS i
+                MERGE (w1:Word {text: $firsts[i]})
+                MERGE (w2:Word {text: $seconds[i]})
+                MERGE (w1)-[r:RELATED]->(w2)
+                  ON CREATE SET r.weight = 0
+                SET r.weight = r.weight + $deltas[i]
+                WITH r WHERE r.weight <= 0
+                DELETE r
+                ",
+            )
+            .param("firsts", firsts)
+            .param("seconds", seconds)
+            .param("deltas", pair_delta_values),
+        );
+    }
+    queries.push(
+        query(
+            "
+            UNWIND range(0, size($urls) - 1) AS i
+            MERGE (site:Website {url: $urls[i]})
+            SET site.snapshot_keywords = $keywords[i],
+                site.snapshot_window = $window_size
+            ",
+        )
+        .param("urls", snapshot_urls)
+        .param("keywords", snapshot_keywords)
+        .param("window_size", window_size as i64),
+    );
+    // last, so the MERGEs above can't bring an unused word back
+    if !shrunk_words.is_empty() {
+        queries.push(
+            query(
+                "
+                UNWIND $texts AS text
+                MATCH (w:Word {text: text})
+                WHERE w.usage <= 0
+                DETACH DELETE w
+                ",
+            )
+            .param("texts", shrunk_words),
+        );
+    }
+
+    let mut txn = neo4j_pool.start_txn().await.or_raise(|| Error::StartTxn)?;
+    txn.run_queries(queries)
+        .await
+        .or_raise(|| Error::RunQueries)?;
+    txn.commit().await.or_raise(|| Error::Commit)?;
+
+    Ok(changed)
+}
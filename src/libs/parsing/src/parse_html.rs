@@ -1,10 +1,21 @@
-use std::{collections::HashSet, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 
 use exn::{Result, ResultExt};
 use scraper::{Html, Selector};
 use url::Url;
 
-use crate::{ParsedHtml, split_into_words::split_into_words};
+use crate::{
+    Heading, PageMetadata, ParsedHtml,
+    tokenizer::{Language, Tokenizer},
+};
+
+const MAX_HEADINGS: usize = 64;
+const MAX_JSON_LD_BLOCKS: usize = 16;
+const MAX_META_VALUE_LEN: usize = 1024;
+const MAX_BODY_TEXT_CHARS: usize = 100_000;
 
 pub async fn parse_html(html: String, url: Url) -> Result<ParsedHtml, Error> {
     let mut urls = HashSet::new();
@@ -34,13 +45,25 @@ pub async fn parse_html(html: String, url: Url) -> Result<ParsedHtml, Error> {
                 continue;
             };
 
-            parsed.set_query(None);
+            // query params are only stripped by the canonicalizer, they can point at different pages
             parsed.set_fragment(None);
 
             urls.insert(parsed);
         }
     }
 
+    let canonical_sel = Selector::parse(r#"link[rel~="canonical"][href]"#)
+        .map_err(|e| HtmlParse(e.to_string()))
+        .or_raise(|| Error::HtmlExtract)?;
+
+    let canonical_url = html
+        .select(&canonical_sel)
+        .next()
+        .and_then(|el| el.value().attr("href"))
+        .and_then(|href| url.join(href.trim()).ok());
+
+    let metadata = extract_metadata(&html)?;
+
     let mut text_parts = Vec::new();
     let root = html.root_element();
 
@@ -68,7 +91,7 @@ pub async fn parse_html(html: String, url: Url) -> Result<ParsedHtml, Error> {
             } else if let Some(text) = node.value().as_text() {
                 let t = text.trim();
                 if !t.is_empty() {
-                    buffer.push(t.to_lowercase());
+                    buffer.push(t.to_owned());
                 }
             }
         }
@@ -76,15 +99,106 @@ pub async fn parse_html(html: String, url: Url) -> Result<ParsedHtml, Error> {
 
     extract_text(root, &mut text_parts);
     let raw_text = text_parts.join(" ");
-    let keywords = split_into_words(&raw_text);
+    let lang = Language::resolve(metadata.lang.as_deref(), &raw_text);
+    let keywords = Tokenizer::default().tokenize(&raw_text, lang);
+    let text = raw_text.chars().take(MAX_BODY_TEXT_CHARS).collect();
 
     Ok(ParsedHtml {
         keywords,
+        text,
         title,
         urls,
+        canonical_url,
+        metadata,
     })
 }
 
+fn extract_metadata(html: &Html) -> Result<PageMetadata, Error> {
+    let lang = html
+        .root_element()
+        .value()
+        .attr("lang")
+        .map(|e| e.trim().to_lowercase())
+        .filter(|e| !e.is_empty());
+
+    let meta_sel = Selector::parse("meta[content]")
+        .map_err(|e| HtmlParse(e.to_string()))
+        .or_raise(|| Error::HtmlExtract)?;
+
+    let mut description = String::new();
+    let mut social = HashMap::new();
+    for el in html.select(&meta_sel) {
+        let Some(key) = el.value().attr("property").or(el.value().attr("name")) else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let content = clean_text(el.value().attr("content").unwrap_or_default());
+        if content.is_empty() {
+            continue;
+        }
+
+        if key == "description" && description.is_empty() {
+            description = content;
+        } else if key.starts_with("og:") || key.starts_with("twitter:") {
+            social.entry(key).or_insert(content);
+        }
+    }
+
+    if description.is_empty() {
+        description = ["og:description", "twitter:description"]
+            .iter()
+            .find_map(|key| social.get(*key))
+            .cloned()
+            .unwrap_or_default();
+    }
+
+    let heading_sel = Selector::parse("h1, h2, h3")
+        .map_err(|e| HtmlParse(e.to_string()))
+        .or_raise(|| Error::HtmlExtract)?;
+
+    let headings = html
+        .select(&heading_sel)
+        .filter_map(|el| {
+            let level = match el.value().name() {
+                "h1" => 1,
+                "h2" => 2,
+                _ => 3,
+            };
+            let text = clean_text(&el.text().collect::<String>());
+            (!text.is_empty()).then_some(Heading { level, text })
+        })
+        .take(MAX_HEADINGS)
+        .collect();
+
+    let json_ld_sel = Selector::parse(r#"script[type="application/ld+json"]"#)
+        .map_err(|e| HtmlParse(e.to_string()))
+        .or_raise(|| Error::HtmlExtract)?;
+
+    // broken json-ld is common, it gets skipped instead of failing the whole page
+    let json_ld = html
+        .select(&json_ld_sel)
+        .filter_map(|el| serde_json::from_str(&el.text().collect::<String>()).ok())
+        .take(MAX_JSON_LD_BLOCKS)
+        .collect();
+
+    Ok(PageMetadata {
+        description,
+        lang,
+        social,
+        headings,
+        json_ld,
+    })
+}
+
+fn clean_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_META_VALUE_LEN)
+        .collect()
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Failed to extract contents from the html")]
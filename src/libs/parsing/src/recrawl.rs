@@ -0,0 +1,162 @@
+use std::{ops::Deref, time::Duration};
+
+use chrono::Utc;
+use exn::{Result, ResultExt};
+use oxalate_scraper_controller::ProxyId;
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+use url::Url;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to read the recrawl state of the url")]
+    SelectUrl,
+
+    #[error("failed to update the recrawl state of the url")]
+    UpdateUrl,
+
+    #[error("failed to add the url to the frontier")]
+    InsertUrl,
+}
+
+/// How often a url is revisited. Every fetch that changed the content halves
+/// the interval, every fetch that did not grows it by half, within the bounds.
+#[derive(Debug, Clone)]
+pub struct RecrawlPolicy {
+    pub initial_interval: Duration,
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+    /// archive the replaced versions of changed pages into `WebpageVersions`
+    pub keep_history: bool,
+}
+
+impl RecrawlPolicy {
+    pub fn next_interval(&self, current: Option<Duration>, changed: bool) -> Duration {
+        let interval = match current {
+            None => self.initial_interval,
+            Some(current) if changed => current / 2,
+            Some(current) => current.saturating_add(current / 2),
+        };
+
+        interval.clamp(self.min_interval, self.max_interval.max(self.min_interval))
+    }
+}
+
+/// The validators of a response, sent back as `If-None-Match` and
+/// `If-Modified-Since` on the next visit.
+#[derive(Debug, Default, Clone)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn from_headers(headers: &Value) -> Self {
+        let header = |name: &str| {
+            headers
+                .as_object()?
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .and_then(|(_, val)| val.as_str())
+                .map(|e| e.to_owned())
+        };
+
+        Self {
+            etag: header("etag"),
+            last_modified: header("last-modified"),
+        }
+    }
+}
+
+pub enum CrawlOutcome<'a> {
+    /// the content hash differs from the stored one, or the page is new
+    Changed { content_hash: &'a str },
+    /// same content hash, or the server answered 304 Not Modified
+    Unchanged,
+}
+
+/// Records a visit of `url` and schedules the next one.
+pub async fn record_visit(
+    db_pool: &Pool<Postgres>,
+    url: &Url,
+    proxy_id: &ProxyId,
+    policy: &RecrawlPolicy,
+    validators: &Validators,
+    outcome: CrawlOutcome<'_>,
+) -> Result<(), Error> {
+    let current = sqlx::query_scalar!(
+        "SELECT recrawl_interval_secs FROM Urls WHERE url = $1;",
+        url.as_str()
+    )
+    .fetch_optional(db_pool)
+    .await
+    .or_raise(|| Error::SelectUrl)?
+    .flatten()
+    .map(|e| Duration::from_secs(e.max(0) as u64));
+
+    let (changed, content_hash) = match outcome {
+        CrawlOutcome::Changed { content_hash } => (true, Some(content_hash)),
+        CrawlOutcome::Unchanged => (false, None),
+    };
+    let interval = policy.next_interval(current, changed);
+    let now = Utc::now().naive_utc();
+    let next_scan_at = now + chrono::Duration::seconds(interval.as_secs() as i64);
+
+    // a 304 carries no new validators, the stored ones stay valid then
+    sqlx::query!(
+        "
+            INSERT INTO Urls
+                (url, last_scanned, device_machine_id, next_scan_at, recrawl_interval_secs,
+                 etag, last_modified, content_hash, change_count, unchanged_count)
+            VALUES
+                ($1, $2, $3, $4, $5, $6, $7, $8, $9::INTEGER, 1 - $9::INTEGER)
+            ON CONFLICT (url) DO UPDATE SET
+                last_scanned = EXCLUDED.last_scanned,
+                next_scan_at = EXCLUDED.next_scan_at,
+                recrawl_interval_secs = EXCLUDED.recrawl_interval_secs,
+                etag = COALESCE(EXCLUDED.etag, Urls.etag),
+                last_modified = COALESCE(EXCLUDED.last_modified, Urls.last_modified),
+                content_hash = COALESCE(EXCLUDED.content_hash, Urls.content_hash),
+                change_count = Urls.change_count + EXCLUDED.change_count,
+                unchanged_count = Urls.unchanged_count + EXCLUDED.unchanged_count;
+        ",
+        url.as_str(),
+        now,
+        proxy_id.deref(),
+        next_scan_at,
+        interval.as_secs() as i32,
+        validators.etag,
+        validators.last_modified,
+        content_hash,
+        changed as i32,
+    )
+    .execute(db_pool)
+    .await
+    .or_raise(|| Error::UpdateUrl)?;
+
+    Ok(())
+}
+
+/// Adds `url` to the frontier, a url that is already known keeps its schedule.
+pub async fn queue_url(
+    db_pool: &Pool<Postgres>,
+    url: &Url,
+    proxy_id: &ProxyId,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "
+            INSERT INTO Urls
+                (url, device_machine_id)
+            VALUES
+                ($1, $2)
+            ON CONFLICT (url) DO NOTHING;
+        ",
+        url.as_str(),
+        proxy_id.deref(),
+    )
+    .execute(db_pool)
+    .await
+    .or_raise(|| Error::InsertUrl)?;
+
+    Ok(())
+}
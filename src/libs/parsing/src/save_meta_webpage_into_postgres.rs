@@ -2,6 +2,8 @@ use exn::{Result, ResultExt};
 use sqlx::{Pool, Postgres};
 use url::Url;
 
+use crate::canonicalize_url::UrlCanonicalizer;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("failed to insert meta page into postgres")]
@@ -17,7 +19,10 @@ pub async fn save_meta_webpage_into_postgres(
     title: &str,
     url: &Url,
     search_engine: &str,
+    canonicalizer: &UrlCanonicalizer,
 ) -> Result<(), Error> {
+    let url = canonicalizer.canonical_page_url(url, None);
+
     sqlx::query!(
         "
             INSERT INTO MetaWebpages
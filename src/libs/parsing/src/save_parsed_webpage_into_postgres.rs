@@ -1,13 +1,19 @@
-use std::{collections::HashMap, ops::Deref};
+use std::{collections::HashSet, ops::Deref};
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use exn::{Result, ResultExt};
+use oxalate_middleware::metrics::{METRICS, QueryStore};
 use oxalate_scraper_controller::ProxyId;
 use serde_json::Value;
 use sqlx::{Pool, Postgres};
 use url::Url;
 
-use crate::ParsedHtml;
+use crate::{
+    ParsedHtml,
+    canonicalize_url::UrlCanonicalizer,
+    fingerprint::{Fingerprint, MAX_SIMHASH_DISTANCE},
+    recrawl::{CrawlOutcome, RecrawlPolicy, Validators, record_visit},
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -16,36 +22,197 @@ pub enum Error {
 
     #[error("Failed to insert webpages into db")]
     InsertUrls,
+
+    #[error("failed to serialize the page metadata into json")]
+    MetadataJson,
+
+    #[error("failed to save the outgoing links of the page")]
+    InsertLinks,
+
+    #[error("failed to look up the duplicates of the page")]
+    FindCluster,
+
+    #[error("failed to look up the stored version of the page")]
+    SelectWebpage,
+
+    #[error("failed to archive the replaced version of the page")]
+    ArchiveWebpage,
+
+    #[error("failed to schedule the next visit of the url")]
+    RecordVisit,
 }
 
+/// Where a page was fetched from.
+pub struct PageSource {
+    pub proxy_id: ProxyId,
+    /// where the body came from
+    pub url: Url,
+    /// the url the proxy was sent to when redirects led to `url`
+    pub requested_url: Option<Url>,
+}
+
+/// Returns whether the page was stored, an unchanged page only gets its visit recorded.
 pub async fn save_parsed_webpage_into_postgres(
     db_pool: &Pool<Postgres>,
     parsed_html: &ParsedHtml,
     compressed_html: &[u8],
     headers_json: Value,
-    proxy_id: ProxyId,
-    url: Url,
-) -> Result<(), Error> {
+    source: PageSource,
+    canonicalizer: &UrlCanonicalizer,
+    recrawl_policy: &RecrawlPolicy,
+) -> Result<bool, Error> {
+    let PageSource {
+        proxy_id,
+        url,
+        requested_url,
+    } = source;
+    // the frontier schedules the requested url, the page itself is stored under
+    // the canonical one of where the redirects led
+    let requested_url = requested_url.as_ref().unwrap_or(&url);
+    let fetched_url = canonicalizer
+        .canonicalize(requested_url)
+        .unwrap_or_else(|| requested_url.to_owned());
+    let url = canonicalizer.canonical_page_url(&url, parsed_html.canonical_url.as_ref());
+    let canonical_url = parsed_html
+        .canonical_url
+        .as_ref()
+        .and_then(|e| canonicalizer.canonicalize(e))
+        .map(|e| e.to_string());
+
+    let metadata = &parsed_html.metadata;
+    let social_meta = serde_json::to_value(&metadata.social).or_raise(|| Error::MetadataJson)?;
+    let headings = serde_json::to_value(&metadata.headings).or_raise(|| Error::MetadataJson)?;
+    let json_ld = serde_json::to_value(&metadata.json_ld).or_raise(|| Error::MetadataJson)?;
+
+    let fingerprint = Fingerprint::new(&parsed_html.text, &parsed_html.keywords);
+    let validators = Validators::from_headers(&headers_json);
+
+    let stored_hash = sqlx::query_scalar!(
+        "SELECT content_hash FROM Webpages WHERE url = $1;",
+        url.as_str()
+    )
+    .fetch_optional(db_pool)
+    .await
+    .or_raise(|| Error::SelectWebpage)?;
+    let changed = match stored_hash {
+        None => true,
+        Some(ref hash) => hash.as_deref() != Some(fingerprint.content_hash.as_str()),
+    };
+
+    let outcome = if changed {
+        CrawlOutcome::Changed {
+            content_hash: &fingerprint.content_hash,
+        }
+    } else {
+        CrawlOutcome::Unchanged
+    };
+    METRICS
+        .time_query(
+            QueryStore::Postgres,
+            "record_visit",
+            record_visit(
+                db_pool,
+                &fetched_url,
+                &proxy_id,
+                recrawl_policy,
+                &validators,
+                outcome,
+            ),
+        )
+        .await
+        .or_raise(|| Error::RecordVisit)?;
+
+    // same text means same keywords and links, nothing to reindex
+    if !changed {
+        return exn::Ok(false);
+    }
+
+    if stored_hash.is_some() && recrawl_policy.keep_history {
+        sqlx::query!(
+            "
+                INSERT INTO WebpageVersions
+                    (url, content_hash, title, keywords, compressed_body, headers, fetched_at)
+                SELECT url, content_hash, title, keywords, compressed_body, headers,
+                    COALESCE(updated_at, created_at)
+                FROM Webpages
+                WHERE url = $1;
+            ",
+            url.as_str(),
+        )
+        .execute(db_pool)
+        .await
+        .or_raise(|| Error::ArchiveWebpage)?;
+    }
+    // pages without words would all end up in one cluster
+    let cluster_id = if parsed_html.keywords.is_empty() {
+        None
+    } else {
+        METRICS
+            .time_query(
+                QueryStore::Postgres,
+                "find_cluster",
+                find_cluster(db_pool, &url, &fingerprint),
+            )
+            .await?
+    };
+
     sqlx::query!(
         "
             INSERT INTO Webpages
-                (url, compressed_body, keywords, headers, device_machine_id, title)
+                (url, compressed_body, keywords, headers, device_machine_id, title,
+                 description, lang, canonical_url, social_meta, headings, json_ld, body_text,
+                 content_hash, simhash, simhash_bands, cluster_id, updated_at)
             VALUES
-                ($1, $2, $3, $4, $5, $6)
-            ON CONFLICT (url) DO NOTHING;   
+                ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+            ON CONFLICT (url) DO UPDATE SET
+                compressed_body = EXCLUDED.compressed_body,
+                keywords = EXCLUDED.keywords,
+                headers = EXCLUDED.headers,
+                device_machine_id = EXCLUDED.device_machine_id,
+                title = EXCLUDED.title,
+                description = EXCLUDED.description,
+                lang = EXCLUDED.lang,
+                canonical_url = EXCLUDED.canonical_url,
+                social_meta = EXCLUDED.social_meta,
+                headings = EXCLUDED.headings,
+                json_ld = EXCLUDED.json_ld,
+                body_text = EXCLUDED.body_text,
+                content_hash = EXCLUDED.content_hash,
+                simhash = EXCLUDED.simhash,
+                simhash_bands = EXCLUDED.simhash_bands,
+                cluster_id = EXCLUDED.cluster_id,
+                updated_at = EXCLUDED.updated_at;
         ",
         url.as_str(),
         compressed_html,
         parsed_html.keywords.join(" "),
         headers_json,
         proxy_id.deref(),
-        parsed_html.title
+        parsed_html.title,
+        metadata.description,
+        metadata.lang,
+        canonical_url,
+        social_meta,
+        headings,
+        json_ld,
+        parsed_html.text,
+        fingerprint.content_hash,
+        fingerprint.simhash as i64,
+        &fingerprint.simhash_bands(),
+        cluster_id.unwrap_or_else(|| url.to_string()),
+        Utc::now().naive_utc(),
     )
     .execute(db_pool)
     .await
     .or_raise(|| Error::InsertWebpages)?;
 
-    for url in parsed_html.urls.iter() {
+    let urls = parsed_html
+        .urls
+        .iter()
+        .filter_map(|e| canonicalizer.canonicalize(e))
+        .collect::<HashSet<_>>();
+
+    for url in urls.iter() {
         let url = url.as_str();
         sqlx::query!(
             "
@@ -64,5 +231,84 @@ pub async fn save_parsed_webpage_into_postgres(
         .or_raise(|| Error::InsertUrls)?;
     }
 
-    exn::Ok(())
+    METRICS
+        .time_query(
+            QueryStore::Postgres,
+            "save_links",
+            save_links(db_pool, &url, &urls),
+        )
+        .await?;
+
+    exn::Ok(true)
+}
+
+/// The cluster of an exact copy of the page, otherwise of its closest
+/// near-duplicate. `None` when the page is the first of its kind.
+async fn find_cluster(
+    db_pool: &Pool<Postgres>,
+    url: &Url,
+    fingerprint: &Fingerprint,
+) -> Result<Option<String>, Error> {
+    // the exact copies first, then the closest near-duplicate
+    let cluster_id = sqlx::query_scalar!(
+        r#"
+            SELECT COALESCE(cluster_id, url) AS "cluster_id!"
+            FROM Webpages
+            WHERE url <> $1
+                AND simhash IS NOT NULL
+                AND (
+                    content_hash = $2
+                    OR (simhash_bands && $3 AND bit_count((simhash # $4)::bit(64)) <= $5)
+                )
+            ORDER BY
+                COALESCE(content_hash = $2, false) DESC,
+                bit_count((simhash # $4)::bit(64))
+            LIMIT 1;
+        "#,
+        url.as_str(),
+        fingerprint.content_hash,
+        &fingerprint.simhash_bands(),
+        fingerprint.simhash as i64,
+        MAX_SIMHASH_DISTANCE as i64,
+    )
+    .fetch_optional(db_pool)
+    .await
+    .or_raise(|| Error::FindCluster)?;
+
+    Ok(cluster_id)
+}
+
+/// Replaces the outgoing edges of `source` in the link graph, self links are left out.
+async fn save_links(
+    db_pool: &Pool<Postgres>,
+    source: &Url,
+    targets: &HashSet<Url>,
+) -> Result<(), Error> {
+    let targets = targets
+        .iter()
+        .filter(|e| *e != source)
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>();
+
+    let mut txn = db_pool.begin().await.or_raise(|| Error::InsertLinks)?;
+    sqlx::query!("DELETE FROM Links WHERE source_url = $1;", source.as_str())
+        .execute(&mut *txn)
+        .await
+        .or_raise(|| Error::InsertLinks)?;
+    sqlx::query!(
+        "
+            INSERT INTO Links
+                (source_url, target_url)
+            SELECT $1, target_url FROM UNNEST($2::TEXT[]) AS target_url
+            ON CONFLICT DO NOTHING;
+        ",
+        source.as_str(),
+        &targets,
+    )
+    .execute(&mut *txn)
+    .await
+    .or_raise(|| Error::InsertLinks)?;
+    txn.commit().await.or_raise(|| Error::InsertLinks)?;
+
+    Ok(())
 }
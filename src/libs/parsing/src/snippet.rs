@@ -0,0 +1,107 @@
+use std::{collections::HashSet, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::tokenizer::{Language, Tokenizer};
+
+pub const DEFAULT_SNIPPET_WORDS: usize = 32;
+
+const ELLIPSIS: &str = "…";
+
+struct Word<'a> {
+    byte_start: usize,
+    text: &'a str,
+    matched: Option<String>,
+}
+
+/// Picks the passage of `text` with `max_words` words that covers the most
+/// distinct query terms, ties go to the passage with more matches overall and
+/// then to the earliest one. Without any match the start of the text is used.
+pub fn build_snippet(
+    text: &str,
+    query_terms: &HashSet<String>,
+    tokenizer: &Tokenizer,
+    lang: Language,
+    max_words: usize,
+) -> String {
+    let words = match_words(text, query_terms, tokenizer, lang);
+    if words.is_empty() {
+        return String::new();
+    }
+
+    let max_words = max_words.max(1);
+    let mut best = (0, 0, 0);
+    for start in 0..words.len().saturating_sub(max_words) + 1 {
+        let window = &words[start..(start + max_words).min(words.len())];
+        let distinct = window
+            .iter()
+            .filter_map(|e| e.matched.as_ref())
+            .collect::<HashSet<_>>()
+            .len();
+        let total = window.iter().filter(|e| e.matched.is_some()).count();
+
+        if (distinct, total) > (best.1, best.2) {
+            best = (start, distinct, total);
+        }
+    }
+
+    let start = best.0;
+    let end = (start + max_words).min(words.len());
+    let byte_start = words[start].byte_start;
+    let byte_end = words[end - 1].byte_start + words[end - 1].text.len();
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str(ELLIPSIS);
+    }
+    snippet.push_str(text[byte_start..byte_end].trim());
+    if end < words.len() {
+        snippet.push_str(ELLIPSIS);
+    }
+    snippet
+}
+
+/// Char ranges (unicode scalar values, not bytes) of every word in `text` that
+/// matches a query term after running through the tokenizer pipeline.
+pub fn highlight_ranges(
+    text: &str,
+    query_terms: &HashSet<String>,
+    tokenizer: &Tokenizer,
+    lang: Language,
+) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut chars_before = 0;
+    let mut bytes_counted = 0;
+
+    for word in match_words(text, query_terms, tokenizer, lang) {
+        if word.matched.is_none() {
+            continue;
+        }
+
+        chars_before += text[bytes_counted..word.byte_start].chars().count();
+        bytes_counted = word.byte_start;
+
+        let len = word.text.chars().count();
+        ranges.push(chars_before..chars_before + len);
+    }
+
+    ranges
+}
+
+fn match_words<'a>(
+    text: &'a str,
+    query_terms: &HashSet<String>,
+    tokenizer: &Tokenizer,
+    lang: Language,
+) -> Vec<Word<'a>> {
+    text.split_word_bound_indices()
+        .filter(|(_, segment)| segment.chars().any(|c| c.is_alphanumeric()))
+        .map(|(byte_start, segment)| Word {
+            byte_start,
+            text: segment,
+            matched: tokenizer
+                .normalize_word(segment, lang)
+                .filter(|token| query_terms.contains(token)),
+        })
+        .collect()
+}
@@ -0,0 +1,112 @@
+use crate::tokenizer::Language;
+
+pub fn stop_words(lang: Language) -> &'static [&'static str] {
+    match lang {
+        Language::English => ENGLISH,
+        Language::German => GERMAN,
+        Language::French => FRENCH,
+        Language::Spanish => SPANISH,
+        Language::Italian => ITALIAN,
+        Language::Portuguese => PORTUGUESE,
+        Language::Dutch => DUTCH,
+        Language::Swedish => SWEDISH,
+        Language::Russian => RUSSIAN,
+        Language::Bulgarian => BULGARIAN,
+        Language::Other => &[],
+    }
+}
+
+#[rustfmt::skip]
+const ENGLISH: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "am", "an", "and", "any", "are",
+    "as", "at", "be", "because", "been", "before", "being", "below", "between", "both", "but",
+    "by", "can", "could", "did", "do", "does", "doing", "down", "during", "each", "few", "for",
+    "from", "further", "had", "has", "have", "having", "he", "her", "here", "hers", "herself",
+    "him", "himself", "his", "how", "i", "if", "in", "into", "is", "it", "its", "itself", "me",
+    "more", "most", "my", "myself", "no", "nor", "not", "of", "off", "on", "once", "only", "or",
+    "other", "our", "ours", "ourselves", "out", "over", "own", "same", "she", "should", "so",
+    "some", "such", "than", "that", "the", "their", "theirs", "them", "themselves", "then",
+    "there", "these", "they", "this", "those", "through", "to", "too", "under", "until", "up",
+    "very", "was", "we", "were", "what", "when", "where", "which", "while", "who", "whom", "why",
+    "will", "with", "would", "you", "your", "yours", "yourself", "yourselves",
+];
+
+#[rustfmt::skip]
+const GERMAN: &[&str] = &[
+    "aber", "alle", "als", "also", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "bist",
+    "da", "damit", "dann", "das", "dass", "dein", "dem", "den", "der", "des", "dich", "die", "dir",
+    "doch", "du", "durch", "ein", "eine", "einem", "einen", "einer", "eines", "er", "es", "euch",
+    "für", "hat", "hatte", "ich", "ihr", "im", "in", "ist", "ja", "kann", "kein", "man", "mich",
+    "mir", "mit", "nach", "nicht", "noch", "nun", "nur", "ob", "oder", "ohne", "sehr", "sein",
+    "sich", "sie", "sind", "so", "um", "und", "uns", "unter", "vom", "von", "vor", "war", "was",
+    "weil", "wenn", "wer", "wie", "wir", "wird", "zu", "zum", "zur", "über",
+];
+
+#[rustfmt::skip]
+const FRENCH: &[&str] = &[
+    "à", "au", "aux", "avec", "ce", "ces", "cette", "dans", "de", "des", "du", "elle", "en", "et",
+    "eux", "il", "ils", "je", "la", "le", "les", "leur", "lui", "ma", "mais", "me", "même", "mes",
+    "moi", "mon", "ne", "nos", "notre", "nous", "on", "ou", "où", "par", "pas", "pour", "qu",
+    "que", "qui", "sa", "se", "ses", "son", "sur", "ta", "te", "tes", "toi", "ton", "tu", "un",
+    "une", "vos", "votre", "vous", "est", "sont", "été", "être", "c", "d", "j", "l", "m", "n",
+    "s", "t", "y",
+];
+
+#[rustfmt::skip]
+const SPANISH: &[&str] = &[
+    "a", "al", "algo", "con", "como", "de", "del", "donde", "el", "ella", "ellos", "en", "entre",
+    "era", "es", "esa", "ese", "eso", "esta", "este", "esto", "está", "fue", "ha", "hay", "la",
+    "las", "le", "les", "lo", "los", "me", "mi", "muy", "más", "ni", "no", "nos", "o", "para",
+    "pero", "por", "que", "qué", "se", "ser", "si", "sin", "sobre", "su", "sus", "también", "te",
+    "tu", "un", "una", "uno", "y", "ya", "yo",
+];
+
+#[rustfmt::skip]
+const ITALIAN: &[&str] = &[
+    "a", "al", "alla", "anche", "che", "chi", "ci", "come", "con", "da", "dal", "dei", "del",
+    "della", "di", "e", "è", "gli", "ha", "i", "il", "in", "io", "la", "le", "lo", "ma", "mi",
+    "ne", "nei", "nel", "nella", "non", "o", "per", "più", "quando", "se", "si", "sono", "su",
+    "sul", "tra", "tu", "un", "una", "uno",
+];
+
+#[rustfmt::skip]
+const PORTUGUESE: &[&str] = &[
+    "a", "ao", "aos", "as", "com", "como", "da", "das", "de", "do", "dos", "e", "é", "ela",
+    "ele", "em", "entre", "era", "essa", "esse", "esta", "este", "eu", "foi", "há", "isso", "já",
+    "mais", "mas", "me", "muito", "na", "não", "nas", "no", "nos", "o", "os", "ou", "para",
+    "pela", "pelo", "por", "que", "se", "sem", "ser", "seu", "sua", "são", "também", "um",
+    "uma",
+];
+
+#[rustfmt::skip]
+const DUTCH: &[&str] = &[
+    "aan", "al", "als", "bij", "dan", "dat", "de", "der", "die", "dit", "door", "een", "en",
+    "er", "had", "heb", "heeft", "het", "hij", "hoe", "ik", "in", "is", "je", "maar", "me", "met",
+    "mij", "na", "naar", "niet", "nog", "nu", "of", "om", "ons", "ook", "op", "over", "te", "tot",
+    "uit", "van", "voor", "was", "wat", "we", "wel", "wie", "wij", "zal", "ze", "zich", "zij",
+    "zijn", "zo",
+];
+
+#[rustfmt::skip]
+const SWEDISH: &[&str] = &[
+    "alla", "att", "av", "de", "dem", "den", "denna", "det", "detta", "du", "där", "efter",
+    "ej", "en", "ett", "för", "från", "ha", "han", "har", "hon", "i", "inte", "jag", "kan",
+    "man", "med", "men", "mig", "min", "mot", "när", "och", "om", "på", "sig", "sin", "som",
+    "så", "till", "under", "upp", "ut", "var", "vi", "vid", "är", "över",
+];
+
+#[rustfmt::skip]
+const RUSSIAN: &[&str] = &[
+    "а", "без", "был", "была", "были", "было", "в", "вы", "да", "для", "до", "его", "ее", "если",
+    "есть", "же", "за", "и", "из", "или", "им", "их", "к", "как", "ко", "кто", "ли", "мы", "на",
+    "не", "нет", "ни", "но", "о", "об", "он", "она", "они", "оно", "от", "по", "при", "с", "со",
+    "так", "то", "только", "ты", "у", "уже", "что", "это", "я",
+];
+
+#[rustfmt::skip]
+const BULGARIAN: &[&str] = &[
+    "а", "без", "бе", "би", "бил", "била", "били", "било", "в", "във", "да", "до", "е", "за",
+    "и", "из", "или", "им", "й", "как", "като", "към", "ли", "му", "на", "не", "ни", "но", "от",
+    "по", "при", "с", "са", "се", "си", "след", "със", "също", "та", "те", "то", "тя", "че",
+    "ще", "я",
+];
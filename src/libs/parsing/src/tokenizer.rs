@@ -0,0 +1,216 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use rust_stemmers::{Algorithm, Stemmer};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::stop_words::stop_words;
+
+/// Used when neither a language hint nor detection gives a usable answer.
+pub const DEFAULT_LANGUAGE: Language = Language::English;
+
+/// One stemmer per language, shared by every tokenizer.
+static STEMMERS: LazyLock<HashMap<Language, Stemmer>> = LazyLock::new(|| {
+    [
+        Language::English,
+        Language::German,
+        Language::French,
+        Language::Spanish,
+        Language::Italian,
+        Language::Portuguese,
+        Language::Dutch,
+        Language::Swedish,
+        Language::Russian,
+    ]
+    .into_iter()
+    .filter_map(|lang| Some((lang, Stemmer::create(lang.stemming_algorithm()?))))
+    .collect()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    Portuguese,
+    Dutch,
+    Swedish,
+    Russian,
+    Bulgarian,
+    /// no stop words and no stemming, cjk and friends end up here
+    Other,
+}
+
+impl Language {
+    /// Parses a BCP 47 tag like `en-US` out of `<html lang>`, only the primary subtag matters.
+    pub fn from_tag(tag: &str) -> Self {
+        let primary = tag
+            .trim()
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match primary.as_str() {
+            "en" | "eng" => Self::English,
+            "de" | "deu" | "ger" => Self::German,
+            "fr" | "fra" | "fre" => Self::French,
+            "es" | "spa" => Self::Spanish,
+            "it" | "ita" => Self::Italian,
+            "pt" | "por" => Self::Portuguese,
+            "nl" | "nld" | "dut" => Self::Dutch,
+            "sv" | "swe" => Self::Swedish,
+            "ru" | "rus" => Self::Russian,
+            "bg" | "bul" => Self::Bulgarian,
+            _ => Self::Other,
+        }
+    }
+
+    pub fn detect(text: &str) -> Option<Self> {
+        let info = whatlang::detect(text)?;
+        if !info.is_reliable() {
+            return None;
+        }
+
+        let lang = match info.lang() {
+            whatlang::Lang::Eng => Self::English,
+            whatlang::Lang::Deu => Self::German,
+            whatlang::Lang::Fra => Self::French,
+            whatlang::Lang::Spa => Self::Spanish,
+            whatlang::Lang::Ita => Self::Italian,
+            whatlang::Lang::Por => Self::Portuguese,
+            whatlang::Lang::Nld => Self::Dutch,
+            whatlang::Lang::Swe => Self::Swedish,
+            whatlang::Lang::Rus => Self::Russian,
+            whatlang::Lang::Bul => Self::Bulgarian,
+            _ => Self::Other,
+        };
+        Some(lang)
+    }
+
+    /// A hint (page lang or the lang of a query) wins, then detection, then the default.
+    pub fn resolve(hint: Option<&str>, text: &str) -> Self {
+        hint.filter(|e| !e.trim().is_empty())
+            .map(Self::from_tag)
+            .or_else(|| Self::detect(text))
+            .unwrap_or(DEFAULT_LANGUAGE)
+    }
+
+    fn stemming_algorithm(&self) -> Option<Algorithm> {
+        match self {
+            Self::English => Some(Algorithm::English),
+            Self::German => Some(Algorithm::German),
+            Self::French => Some(Algorithm::French),
+            Self::Spanish => Some(Algorithm::Spanish),
+            Self::Italian => Some(Algorithm::Italian),
+            Self::Portuguese => Some(Algorithm::Portuguese),
+            Self::Dutch => Some(Algorithm::Dutch),
+            Self::Swedish => Some(Algorithm::Swedish),
+            Self::Russian => Some(Algorithm::Russian),
+            Self::Bulgarian | Self::Other => None,
+        }
+    }
+}
+
+/// One step of the tokenizer pipeline, returning `None` drops the token.
+pub trait TokenFilter: Send + Sync {
+    fn apply(&self, token: String, lang: Language) -> Option<String>;
+}
+
+pub struct StopWordFilter;
+
+impl TokenFilter for StopWordFilter {
+    fn apply(&self, token: String, lang: Language) -> Option<String> {
+        (!stop_words(lang).contains(&token.as_str())).then_some(token)
+    }
+}
+
+/// Keywords stored before stemming was added stay unstemmed, their pages only
+/// match stemmed queries again once they are recrawled and stored anew.
+pub struct StemFilter;
+
+impl TokenFilter for StemFilter {
+    fn apply(&self, token: String, lang: Language) -> Option<String> {
+        let Some(stemmer) = STEMMERS.get(&lang) else {
+            return Some(token);
+        };
+        Some(stemmer.stem(&token).into_owned())
+    }
+}
+
+/// Folds latin letters with diacritics onto their ascii base, `é` becomes `e`.
+/// Letters whose base is not ascii are kept as is, so cyrillic `й` stays `й`.
+pub struct FoldDiacriticsFilter;
+
+impl TokenFilter for FoldDiacriticsFilter {
+    fn apply(&self, token: String, _lang: Language) -> Option<String> {
+        if token.is_ascii() {
+            return Some(token);
+        }
+
+        let folded = token
+            .chars()
+            .map(|c| match std::iter::once(c).nfd().next() {
+                Some(base) if base.is_ascii() => base,
+                _ => c,
+            })
+            .collect();
+        Some(folded)
+    }
+}
+
+/// Unicode word segmentation and NFKC/case folding, followed by the token filters.
+pub struct Tokenizer {
+    filters: Vec<Box<dyn TokenFilter>>,
+}
+
+impl Tokenizer {
+    /// A tokenizer without any filters, only segmentation and normalization.
+    pub fn new() -> Self {
+        Self { filters: vec![] }
+    }
+
+    pub fn with_filter(mut self, filter: impl TokenFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    pub fn tokenize(&self, text: &str, lang: Language) -> Vec<String> {
+        if text.is_empty() {
+            return vec![];
+        }
+
+        let normalized = text.nfkc().collect::<String>().to_lowercase();
+        normalized
+            .unicode_words()
+            .filter_map(|word| self.apply_filters(word.to_owned(), lang))
+            .collect()
+    }
+
+    /// Runs a single already segmented word through the pipeline, used to match
+    /// words of a stored text against tokenized query terms.
+    pub fn normalize_word(&self, word: &str, lang: Language) -> Option<String> {
+        let normalized = word.nfkc().collect::<String>().to_lowercase();
+        self.apply_filters(normalized, lang)
+    }
+
+    fn apply_filters(&self, word: String, lang: Language) -> Option<String> {
+        self.filters
+            .iter()
+            .try_fold(word, |token, filter| filter.apply(token, lang))
+            .filter(|token| !token.is_empty())
+    }
+}
+
+impl Default for Tokenizer {
+    /// stop words, stemming and diacritic folding, in that order since the stop
+    /// word lists and the stemmers expect the diacritics to still be there
+    fn default() -> Self {
+        Self::new()
+            .with_filter(StopWordFilter)
+            .with_filter(StemFilter)
+            .with_filter(FoldDiacriticsFilter)
+    }
+}
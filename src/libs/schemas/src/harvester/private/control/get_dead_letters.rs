@@ -0,0 +1,9 @@
+use oxalate_scraper_controller::dead_letters::DeadLetter;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = Get::Control::DeadLetters::Res)]
+pub struct Res {
+    pub dead_letters: Vec<DeadLetter>,
+}
@@ -0,0 +1,22 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Get::Control::EnrollmentTokens::Res)]
+pub struct Res {
+    pub tokens: Vec<EnrollmentToken>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Get::Control::EnrollmentTokens::Res::EnrollmentToken)]
+pub struct EnrollmentToken {
+    pub id: Uuid,
+    pub label: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    /// devices that authenticated with the token
+    pub devices: i64,
+}
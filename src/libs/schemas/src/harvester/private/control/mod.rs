@@ -1,2 +1,9 @@
+pub mod get_dead_letters;
+pub mod get_enrollment_tokens;
 pub mod get_scraper_state;
+pub mod post_enrollment_token;
+pub mod post_replay_dead_letters;
+pub mod post_reset_worker_trust;
+pub mod post_revoke_enrollment_token;
+pub mod post_spot_checks;
 pub mod post_swap_scraper_on_state;
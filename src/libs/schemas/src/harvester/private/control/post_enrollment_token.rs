@@ -0,0 +1,21 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// the token never expires when `expires_in_secs` is missing
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::EnrollmentToken::Req)]
+pub struct Req {
+    pub label: String,
+    pub expires_in_secs: Option<u64>,
+}
+
+/// `token` is only ever shown here, workers send it as `Authorization: Bearer <token>`
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::EnrollmentToken::Res)]
+pub struct Res {
+    pub id: Uuid,
+    pub token: String,
+    pub expires_at: Option<NaiveDateTime>,
+}
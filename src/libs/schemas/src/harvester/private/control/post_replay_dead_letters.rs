@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// replays every dead letter when `ids` is missing
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::ReplayDeadLetters::Req)]
+pub struct Req {
+    pub ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::ReplayDeadLetters::Res)]
+pub struct Res {
+    pub replayed: usize,
+}
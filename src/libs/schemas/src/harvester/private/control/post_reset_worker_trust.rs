@@ -0,0 +1,16 @@
+use oxalate_scraper_controller::ProxyId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::ResetWorkerTrust::Req)]
+pub struct Req {
+    pub proxy_id: ProxyId,
+}
+
+/// `reset` is false when the proxy had no trust recorded
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::ResetWorkerTrust::Res)]
+pub struct Res {
+    pub reset: bool,
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::RevokeEnrollmentToken::Req)]
+pub struct Req {
+    pub id: Uuid,
+}
+
+/// `revoked` is false when the token does not exist or was already revoked
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::RevokeEnrollmentToken::Res)]
+pub struct Res {
+    pub revoked: bool,
+}
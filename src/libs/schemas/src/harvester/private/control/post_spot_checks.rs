@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// `fraction` is clamped between 0 and 1, 0 disables spot checks
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::SpotChecks::Req)]
+pub struct Req {
+    pub fraction: f64,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = Post::Control::SpotChecks::Res)]
+pub struct Res {
+    pub fraction: f64,
+}
@@ -0,0 +1,11 @@
+use std::collections::HashMap;
+
+use oxalate_scraper_controller::host_scheduler::HostBudget;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = Get::Metric::HostBudgets::Res)]
+pub struct Res {
+    pub host_budgets: HashMap<String, HostBudget>,
+}
@@ -0,0 +1,13 @@
+use std::collections::HashMap;
+
+use oxalate_scraper_controller::integrity::WorkerTrust;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = Get::Metric::WorkerTrust::Res)]
+pub struct Res {
+    pub spot_check_fraction: f64,
+    /// keyed on proxy id, proxies that were never checked are left out
+    pub worker_trust: HashMap<String, WorkerTrust>,
+}
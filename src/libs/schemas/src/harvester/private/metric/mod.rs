@@ -1,3 +1,7 @@
 pub mod get_connected_proxies;
 
 pub mod get_active_tasks;
+
+pub mod get_host_budgets;
+
+pub mod get_worker_trust;
@@ -5,6 +5,9 @@ use utoipa::ToSchema;
 #[schema(as = Post::KeywordGraph::Req)]
 pub struct Req {
     pub text: String,
+    /// BCP 47 tag of the query language, detected from the text when missing
+    #[serde(default)]
+    pub lang: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, ToSchema, Debug)]
@@ -4,16 +4,67 @@ use serde::{Deserialize, Serialize};
 use url::Url;
 use utoipa::ToSchema;
 
+pub const DEFAULT_PAGE_SIZE: usize = 10;
+pub const MAX_PAGE_SIZE: usize = 100;
+/// `offset + page_size` can't go past this, deeper results aren't fetched from the engines.
+pub const MAX_RESULT_DEPTH: usize = 500;
+
 #[derive(Deserialize, Serialize, ToSchema, Debug)]
 #[schema(as = Post::Search::Req)]
 pub struct Req {
     pub text: String,
+    /// BCP 47 tag of the query language, detected from the text when missing
+    #[serde(default)]
+    pub lang: Option<String>,
+
+    /// zero based, ignored when `offset` is set
+    #[serde(default)]
+    pub page: usize,
+    /// `offset + page_size` must stay at or below `MAX_RESULT_DEPTH` (500)
+    #[serde(default)]
+    pub offset: Option<usize>,
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+
+    /// weight per engine name used when merging, engines that are missing get 1.0
+    #[serde(default)]
+    pub weights: HashMap<String, f32>,
+    /// names of the engines to query, every enabled engine when missing
+    #[serde(default)]
+    pub engines: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Serialize, ToSchema, Debug)]
 #[schema(as = Post::Search::Res)]
 pub struct Res {
+    /// the merged and deduplicated results of the requested page
+    pub results: Vec<RankedSearchResult>,
+    /// amount of merged results across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub page_size: usize,
+
+    /// the raw results of every engine
     pub search_results: HashMap<String, Vec<SearchResult>>,
+    pub engine_statuses: HashMap<String, EngineStatus>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug)]
+#[schema(as = Post::Search::Res::EngineStatus)]
+pub struct EngineStatus {
+    pub state: EngineState,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+#[schema(as = Post::Search::Res::EngineState)]
+pub enum EngineState {
+    Ok,
+    Timeout,
+    Error,
+    CircuitOpen,
 }
 
 #[derive(Deserialize, Serialize, ToSchema, Debug)]
@@ -22,4 +73,29 @@ pub struct SearchResult {
     pub url: Url,
     pub text: String,
     pub title: String,
+    pub highlights: Vec<HighlightRange>,
+}
+
+/// Matched query terms in `text`, as char offsets (unicode scalar values) with an exclusive end.
+#[derive(Deserialize, Serialize, ToSchema, Debug)]
+#[schema(as = Post::Search::Res::HighlightRange)]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug)]
+#[schema(as = Post::Search::Res::RankedSearchResult)]
+pub struct RankedSearchResult {
+    pub url: Url,
+    pub text: String,
+    pub title: String,
+    pub score: f32,
+    /// engines that returned this result
+    pub sources: Vec<String>,
+    pub highlights: Vec<HighlightRange>,
+}
+
+fn default_page_size() -> usize {
+    DEFAULT_PAGE_SIZE
 }
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Deserialize, Serialize, ToSchema, Debug)]
+#[schema(as = Get::IngestMetrics::Res)]
+pub struct Res {
+    pub pages_ingested: u64,
+    pub batches: u64,
+    pub failed_batches: u64,
+    /// changed word usages and CONTAINS edges
+    pub words_written: u64,
+    /// changed RELATED edges
+    pub pairs_written: u64,
+    /// pages per second while the neo4j writer was busy
+    pub pages_per_sec: f64,
+    pub last_batch_ms: u64,
+    pub queued_pages: usize,
+}
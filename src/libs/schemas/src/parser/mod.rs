@@ -1,2 +1,4 @@
+pub mod get_ingest_metrics;
 pub mod post_insert_meta_webpage;
 pub mod post_insert_webpage;
+pub mod proxy_res_batch;
@@ -14,8 +14,18 @@ pub struct Req {
 #[derive(Deserialize, Serialize, ToSchema, Debug)]
 #[schema(as = Post::ParseAndInsert::Req::Page)]
 pub struct Page {
+    /// where the body came from, the page is stored under it
     pub url: Url,
-    pub raw_html: String,
+    /// the url the proxy was sent to when redirects led to `url`, its frontier
+    /// row is the one that gets the visit and the next schedule
+    #[serde(default)]
+    pub requested_url: Option<Url>,
+    /// the raw response body as base64, decoded with `content_type` by the parser
+    #[serde(with = "oxalate_scraper_controller::base64_body")]
+    #[schema(value_type = String, format = Byte)]
+    pub body: Vec<u8>,
+    #[serde(default)]
+    pub content_type: Option<String>,
     pub headers: Option<HashMap<String, String>>,
     pub proxy_id: ProxyId,
 }
@@ -0,0 +1,13 @@
+use oxalate_scraper_controller::{ProxyId, scraper_controller::ProxyRes};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A message on the proxy res topic, published by the harvester once a proxy
+/// returns its task outputs and consumed by the parser.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProxyResBatch {
+    /// the same for every message a too big batch was split into
+    pub id: Uuid,
+    pub proxy_id: ProxyId,
+    pub outputs: Vec<ProxyRes>,
+}
@@ -0,0 +1,91 @@
+use chrono::{NaiveDateTime, Utc};
+use dashmap::DashMap;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{ProxyId, scraper_controller::ProxyReq};
+
+const MAX_DEAD_LETTERS: usize = 100_000;
+
+/// Proxy reqs that failed more than the max attempt count. They stay here until
+/// an operator replays or drops them through the private control api.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DeadLetterStore {
+    letters: DashMap<Uuid, DeadLetter>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct DeadLetter {
+    pub id: Uuid,
+    pub req: ProxyReq,
+    pub attempts: u32,
+    pub reason: String,
+    pub task_id: Uuid,
+    pub proxy_id: ProxyId,
+    pub failed_at: NaiveDateTime,
+}
+
+impl DeadLetterStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<LoggingCTX: Serialize>(
+        &self,
+        req: ProxyReq,
+        reason: &str,
+        task_id: Uuid,
+        proxy_id: &ProxyId,
+        logging_ctx: &LoggingCTX,
+    ) {
+        if self.letters.len() >= MAX_DEAD_LETTERS {
+            warn!(ctx:serde = logging_ctx; "dead letter store is full, dropping proxy req");
+            return;
+        }
+
+        let id = Uuid::new_v4();
+        let letter = DeadLetter {
+            id,
+            attempts: req.attempts(),
+            req,
+            reason: reason.to_owned(),
+            task_id,
+            proxy_id: proxy_id.to_owned(),
+            failed_at: Utc::now().naive_utc(),
+        };
+        self.letters.insert(id, letter);
+    }
+
+    pub fn list(&self) -> Vec<DeadLetter> {
+        let mut letters = self
+            .letters
+            .iter()
+            .map(|e| e.value().to_owned())
+            .collect::<Vec<_>>();
+        letters.sort_by_key(|e| e.failed_at);
+        letters
+    }
+
+    /// Removes and returns the given dead letters, or all of them when `ids` is `None`.
+    pub fn take(&self, ids: Option<&[Uuid]>) -> Vec<DeadLetter> {
+        let ids = match ids {
+            Some(ids) => ids.to_vec(),
+            None => self.letters.iter().map(|e| *e.key()).collect(),
+        };
+
+        ids.iter()
+            .filter_map(|id| self.letters.remove(id))
+            .map(|(_, letter)| letter)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.letters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.letters.is_empty()
+    }
+}
@@ -11,7 +11,12 @@ use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use crate::scraper_controller::{HttpMethod, HttpReq, ProxyReq, ProxyTask, ProxyTaskGenerator};
+use crate::{
+    host_scheduler::url_host_key,
+    scraper_controller::{
+        FetchLimits, HttpMethod, HttpReq, ProxyReq, ProxyTask, ProxyTaskGenerator,
+    },
+};
 use thiserror::Error;
 
 use exn::{Result, ResultExt};
@@ -19,6 +24,8 @@ use exn::{Result, ResultExt};
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FileIteratorTaskGenerator {
     urls: Mutex<VecDeque<Box<[Url]>>>,
+    #[serde(default)]
+    limits: FetchLimits,
 }
 
 #[derive(Debug, Error)]
@@ -28,12 +35,13 @@ pub enum Error {
 }
 
 impl FileIteratorTaskGenerator {
-    pub fn new(path: &PathBuf, job_size: usize) -> Result<Self, Error> {
+    pub fn new(path: &PathBuf, job_size: usize, limits: FetchLimits) -> Result<Self, Error> {
         let file = fs::read_to_string(path).or_raise(|| Error::FailedToBuild)?;
         let urls = file
             .lines()
             .filter_map(|e| Url::parse(&format!("https://{e}")).ok())
-            .collect::<Box<_>>();
+            .collect::<Vec<_>>();
+        let urls = interleave_by_host(urls);
 
         let mut queue = VecDeque::with_capacity(urls.len() / job_size);
         for chunk in urls.chunks(job_size) {
@@ -44,8 +52,45 @@ impl FileIteratorTaskGenerator {
 
         Ok(Self {
             urls: Mutex::new(queue),
+            limits,
         })
     }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.urls.lock().is_empty()
+    }
+}
+
+// round robins over the hosts so the urls of one host end up spread over many chunks
+// instead of filling a whole chunk
+fn interleave_by_host(urls: Vec<Url>) -> Vec<Url> {
+    let mut host_order = vec![];
+    let mut by_host: HashMap<String, VecDeque<Url>> = HashMap::new();
+    for url in urls {
+        let host = url_host_key(&url).unwrap_or_default();
+        by_host
+            .entry(host.to_owned())
+            .or_insert_with(|| {
+                host_order.push(host);
+                VecDeque::new()
+            })
+            .push_back(url);
+    }
+
+    let mut interleaved = Vec::with_capacity(by_host.values().map(|e| e.len()).sum());
+    while !host_order.is_empty() {
+        host_order.retain(
+            |host| match by_host.get_mut(host).and_then(|e| e.pop_front()) {
+                Some(url) => {
+                    interleaved.push(url);
+                    true
+                }
+                None => false,
+            },
+        );
+    }
+
+    interleaved
 }
 
 #[async_trait]
@@ -78,13 +123,17 @@ impl ProxyTaskGenerator<Infallible> for FileIteratorTaskGenerator {
                     body: String::new(),
                     headers: HashMap::new(),
                     method: HttpMethod::Get,
+                    attempts: 0,
+                    validators: Default::default(),
+                    limits: self.limits.to_owned(),
+                    delay_ms: 0,
                 };
 
                 ProxyReq::Http(http_req)
             })
             .collect();
 
-        let task = ProxyTask { proxy_reqs: reqs };
+        let task = ProxyTask::new(reqs);
 
         info!(
             ctx:serde = logging_ctx;
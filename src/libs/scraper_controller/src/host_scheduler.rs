@@ -0,0 +1,298 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use dashmap::DashMap;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use utoipa::ToSchema;
+
+use crate::{
+    robots::RobotsCache,
+    scraper_controller::{ProxyReq, ProxyTask},
+};
+
+const DEFAULT_CRAWL_DELAY_MS: u64 = 1000;
+const DEFAULT_MAX_REQS_PER_HOST_PER_TASK: usize = 4;
+const DEFAULT_MAX_REQS_PER_TASK: usize = 512;
+const DEFAULT_TASK_LEASE_MS: u64 = 900_000;
+const MAX_DEFERRED_REQS_PER_HOST: usize = 10_000;
+
+/// Spreads requests to the same host across tasks and time. Every host gets at
+/// most `max_reqs_per_host_per_task` requests per task and is not handed out
+/// again before its crawl-delay passed, everything above that is deferred.
+/// The reqs of one host within a task carry a `delay_ms` one crawl-delay apart,
+/// so crawl-delays are capped to still fit all of them into the task lease.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HostScheduler {
+    default_crawl_delay_ms: AtomicU64,
+    max_reqs_per_host_per_task: AtomicUsize,
+    #[serde(default = "default_max_reqs_per_task")]
+    max_reqs_per_task: AtomicUsize,
+    #[serde(default = "default_task_lease_ms")]
+    task_lease_ms: AtomicU64,
+    hosts: DashMap<String, HostState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct HostState {
+    next_allowed_at: NaiveDateTime,
+    dispatched: u64,
+    deferred: VecDeque<ProxyReq>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct HostBudget {
+    pub next_allowed_at: NaiveDateTime,
+    pub crawl_delay_ms: u64,
+    pub max_reqs_per_task: usize,
+    pub dispatched: u64,
+    pub deferred: usize,
+}
+
+impl HostScheduler {
+    pub fn new() -> Self {
+        Self {
+            default_crawl_delay_ms: DEFAULT_CRAWL_DELAY_MS.into(),
+            max_reqs_per_host_per_task: DEFAULT_MAX_REQS_PER_HOST_PER_TASK.into(),
+            max_reqs_per_task: default_max_reqs_per_task(),
+            task_lease_ms: default_task_lease_ms(),
+            hosts: DashMap::new(),
+        }
+    }
+
+    pub fn set_default_crawl_delay_ms(&self, crawl_delay_ms: u64) {
+        self.default_crawl_delay_ms
+            .store(crawl_delay_ms, Ordering::Relaxed);
+    }
+
+    pub fn set_max_reqs_per_host_per_task(&self, max_reqs: usize) {
+        self.max_reqs_per_host_per_task
+            .store(max_reqs.max(1), Ordering::Relaxed);
+    }
+
+    /// The job size of the tasks, the deferred reqs never make a task bigger than it.
+    pub fn set_max_reqs_per_task(&self, max_reqs: usize) {
+        self.max_reqs_per_task
+            .store(max_reqs.max(1), Ordering::Relaxed);
+    }
+
+    /// The lease of the tasks, the reqs of a host have to be sent before it runs out.
+    pub fn set_task_lease_secs(&self, lease_secs: u64) {
+        self.task_lease_ms
+            .store(lease_secs.saturating_mul(1000), Ordering::Relaxed);
+    }
+
+    /// Builds the task that is actually handed out: ready deferred requests go
+    /// first, then the freshly generated ones, as long as each host has budget.
+    /// Deferred requests wait until the robots.txt of their host is known and
+    /// are dropped when it disallows them.
+    pub fn schedule<LoggingCTX: Serialize>(
+        &self,
+        task: ProxyTask,
+        robots_cache: &RobotsCache,
+        logging_ctx: &LoggingCTX,
+    ) -> ProxyTask {
+        let now = Utc::now().naive_utc();
+        let max_reqs = self.max_reqs_per_host_per_task.load(Ordering::Relaxed);
+        let max_task_reqs = self.max_reqs_per_task.load(Ordering::Relaxed);
+
+        let mut proxy_reqs = vec![];
+        let mut used_in_task = HashMap::new();
+        let mut disallowed = 0;
+        for mut host in self.hosts.iter_mut() {
+            if proxy_reqs.len() >= max_task_reqs {
+                break;
+            }
+            let host_state = host.value_mut();
+            let is_ready = host_state
+                .deferred
+                .front()
+                .is_some_and(|req| robots_cache.is_known(req.url()));
+            if host_state.next_allowed_at > now || !is_ready {
+                continue;
+            }
+
+            let take = host_state
+                .deferred
+                .len()
+                .min(max_reqs)
+                .min(max_task_reqs - proxy_reqs.len());
+            let mut reqs = host_state.deferred.drain(..take).collect::<Vec<_>>();
+            reqs.retain(|req| robots_cache.is_allowed(req.url()));
+            disallowed += take - reqs.len();
+            let take = reqs.len();
+            if take == 0 {
+                continue;
+            }
+            let delay = self.crawl_delay(&reqs[0], robots_cache);
+            for (i, req) in reqs.iter_mut().enumerate() {
+                set_delay(req, delay * i as i32);
+            }
+            host_state.next_allowed_at = now + delay * take as i32;
+            host_state.dispatched += take as u64;
+            used_in_task.insert(host.key().to_owned(), take);
+            proxy_reqs.extend(reqs);
+        }
+        let from_deferred = proxy_reqs.len();
+
+        let mut deferred = 0;
+        for mut req in task.proxy_reqs {
+            let Some(key) = host_key(&req) else {
+                proxy_reqs.push(req);
+                continue;
+            };
+            let delay = self.crawl_delay(&req, robots_cache);
+            let used = used_in_task.entry(key.to_owned()).or_insert(0);
+
+            let mut host_state = self.hosts.entry(key).or_insert_with(|| HostState {
+                next_allowed_at: now,
+                dispatched: 0,
+                deferred: VecDeque::new(),
+            });
+
+            let has_budget = proxy_reqs.len() < max_task_reqs
+                && *used < max_reqs
+                && host_state.deferred.is_empty()
+                && (*used > 0 || host_state.next_allowed_at <= now);
+
+            if has_budget {
+                // the outlet fires the whole task at once, so the req waits for its turn there
+                let starts_at = host_state.next_allowed_at.max(now);
+                set_delay(&mut req, starts_at - now);
+                host_state.next_allowed_at = starts_at + delay;
+                host_state.dispatched += 1;
+                *used += 1;
+                proxy_reqs.push(req);
+            } else if host_state.deferred.len() < MAX_DEFERRED_REQS_PER_HOST {
+                host_state.deferred.push_back(req);
+                deferred += 1;
+            } else {
+                warn!(ctx:serde = logging_ctx; "deferred queue for host is full, dropping proxy req");
+            }
+        }
+
+        info!(
+            ctx:serde = logging_ctx;
+            "host scheduler built task with {} reqs ({} from deferred), deferred {} reqs, dropped {} disallowed deferred reqs",
+            proxy_reqs.len(), from_deferred, deferred, disallowed
+        );
+        ProxyTask::new(proxy_reqs.into_boxed_slice())
+    }
+
+    /// Queues a req behind the other deferred reqs of its host, used for retries.
+    pub fn defer<LoggingCTX: Serialize>(&self, req: ProxyReq, logging_ctx: &LoggingCTX) {
+        let Some(key) = host_key(&req) else {
+            warn!(ctx:serde = logging_ctx; "can't defer a proxy req without a host, dropping it");
+            return;
+        };
+
+        let mut host_state = self.hosts.entry(key).or_insert_with(|| HostState {
+            next_allowed_at: Utc::now().naive_utc(),
+            dispatched: 0,
+            deferred: VecDeque::new(),
+        });
+
+        if host_state.deferred.len() < MAX_DEFERRED_REQS_PER_HOST {
+            host_state.deferred.push_back(req);
+        } else {
+            warn!(ctx:serde = logging_ctx; "deferred queue for host is full, dropping proxy req");
+        }
+    }
+
+    /// A url of every host whose deferred reqs are due, at most `max_reqs_per_task`
+    /// of them, so the robots.txt of their hosts can be refreshed before scheduling.
+    pub fn ready_urls(&self) -> Vec<Url> {
+        let now = Utc::now().naive_utc();
+        let max_task_reqs = self.max_reqs_per_task.load(Ordering::Relaxed);
+        self.hosts
+            .iter()
+            .filter(|host| host.next_allowed_at <= now)
+            .filter_map(|host| host.deferred.front().map(|req| req.url().to_owned()))
+            .take(max_task_reqs)
+            .collect()
+    }
+
+    pub fn evict_idle<LoggingCTX: Serialize>(&self, logging_ctx: &LoggingCTX) {
+        let now = Utc::now().naive_utc();
+        let before = self.hosts.len();
+        self.hosts
+            .retain(|_, host| !host.deferred.is_empty() || host.next_allowed_at > now);
+        debug!(ctx:serde = logging_ctx; "evicted {} idle hosts from host scheduler", before - self.hosts.len());
+    }
+
+    pub fn budgets(&self, robots_cache: &RobotsCache) -> Vec<(String, HostBudget)> {
+        let max_reqs = self.max_reqs_per_host_per_task.load(Ordering::Relaxed);
+        let default_delay = self.default_crawl_delay_ms.load(Ordering::Relaxed);
+
+        self.hosts
+            .iter()
+            .map(|host| {
+                let crawl_delay_ms = host
+                    .deferred
+                    .front()
+                    .map(|req| self.crawl_delay(req, robots_cache).num_milliseconds() as u64)
+                    .unwrap_or(default_delay);
+
+                let budget = HostBudget {
+                    next_allowed_at: host.next_allowed_at,
+                    crawl_delay_ms,
+                    max_reqs_per_task: max_reqs,
+                    dispatched: host.dispatched,
+                    deferred: host.deferred.len(),
+                };
+                (host.key().to_owned(), budget)
+            })
+            .collect()
+    }
+
+    /// The crawl-delay of the host of `req`, capped so the last of the
+    /// `max_reqs_per_host_per_task` reqs is sent with a delay to spare of the lease.
+    fn crawl_delay(&self, req: &ProxyReq, robots_cache: &RobotsCache) -> Duration {
+        let robots_delay = match req {
+            ProxyReq::Http(http_req) => robots_cache.crawl_delay(&http_req.url),
+        };
+
+        let delay_ms = match robots_delay {
+            // saturates, robots.rs only keeps finite delays that are not negative
+            Some(secs) => (secs * 1000.0) as u64,
+            None => self.default_crawl_delay_ms.load(Ordering::Relaxed),
+        };
+        let max_reqs = self.max_reqs_per_host_per_task.load(Ordering::Relaxed) as u64;
+        let max_delay_ms = self.task_lease_ms.load(Ordering::Relaxed) / (max_reqs + 1);
+        Duration::milliseconds(delay_ms.min(max_delay_ms) as i64)
+    }
+}
+
+impl Default for HostScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn default_max_reqs_per_task() -> AtomicUsize {
+    DEFAULT_MAX_REQS_PER_TASK.into()
+}
+
+fn default_task_lease_ms() -> AtomicU64 {
+    DEFAULT_TASK_LEASE_MS.into()
+}
+
+fn set_delay(req: &mut ProxyReq, delay: Duration) {
+    match req {
+        ProxyReq::Http(http_req) => http_req.delay_ms = delay.num_milliseconds().max(0) as u64,
+    }
+}
+
+pub fn url_host_key(url: &Url) -> Option<String> {
+    url.host_str().map(|e| e.to_lowercase())
+}
+
+fn host_key(req: &ProxyReq) -> Option<String> {
+    match req {
+        ProxyReq::Http(http_req) => url_host_key(&http_req.url),
+    }
+}
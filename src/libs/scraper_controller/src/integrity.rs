@@ -0,0 +1,294 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use dashmap::DashMap;
+use log::{debug, info, warn};
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    ProxyId,
+    scraper_controller::{HttpRes, ProxyReq},
+    simhash::simhash,
+};
+
+/// Unfinished spot checks above this are not sampled, so a stalled fleet can't grow the map forever.
+const MAX_PENDING_SPOT_CHECKS: usize = 10_000;
+/// Spot checks that were not settled within this are dropped, their req most likely ran out of attempts.
+const SPOT_CHECK_TTL: Duration = Duration::hours(1);
+/// Two fetches whose text simhashes differ in at most this many bits are the
+/// same page, dates, counters and ads on dynamic pages move a few bits.
+const MAX_SPOT_CHECK_DISTANCE: u32 = 6;
+/// Multiplied into the trust of a worker per return that holds results outside
+/// of its leased task, no matter how many.
+const UNSOLICITED_PENALTY: f64 = 0.8;
+/// Multiplied into the trust of a worker per spot check that came back different,
+/// dynamic pages can differ between fetches so a single mismatch is not fatal.
+const MISMATCH_PENALTY: f64 = 0.7;
+/// Added to the trust of a worker per spot check that came back the same.
+const MATCH_REWARD: f64 = 0.05;
+
+/// Checks the results workers hand back. Results for urls outside of the
+/// leased task are rejected, and a sampled fraction of the accepted ones is
+/// fetched again by another worker to compare the simhashes of their text. Both
+/// feed a per worker trust score, workers below the minimum trust get
+/// everything rejected until their trust is reset.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IntegrityChecker {
+    /// f64 bits, 0 disables spot checks
+    #[serde(default)]
+    spot_check_fraction: AtomicU64,
+    #[serde(default = "default_min_trust")]
+    min_trust: AtomicU64,
+    #[serde(default)]
+    trust: DashMap<ProxyId, WorkerTrust>,
+    #[serde(default)]
+    pending_spot_checks: DashMap<Url, PendingSpotCheck>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct WorkerTrust {
+    /// between 0 and 1, new workers start fully trusted
+    pub score: f64,
+    pub unsolicited_results: u64,
+    pub spot_checks_passed: u64,
+    pub spot_checks_failed: u64,
+}
+
+impl Default for WorkerTrust {
+    fn default() -> Self {
+        Self {
+            score: 1.0,
+            unsolicited_results: 0,
+            spot_checks_passed: 0,
+            spot_checks_failed: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PendingSpotCheck {
+    proxy_id: ProxyId,
+    #[serde(default)]
+    simhash: u64,
+    sampled_at: NaiveDateTime,
+}
+
+/// What a worker result should be used for.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    /// the fetch only served as a spot check of another worker, it is not stored again
+    SpotCheck,
+    Reject,
+}
+
+impl IntegrityChecker {
+    pub fn new() -> Self {
+        Self {
+            spot_check_fraction: AtomicU64::new(0f64.to_bits()),
+            min_trust: default_min_trust(),
+            trust: DashMap::new(),
+            pending_spot_checks: DashMap::new(),
+        }
+    }
+
+    pub fn set_spot_check_fraction(&self, fraction: f64) {
+        let fraction = if fraction.is_finite() {
+            fraction.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        self.spot_check_fraction
+            .store(fraction.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn spot_check_fraction(&self) -> f64 {
+        f64::from_bits(self.spot_check_fraction.load(Ordering::Relaxed))
+    }
+
+    pub fn set_min_trust(&self, min_trust: f64) {
+        self.min_trust
+            .store(min_trust.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn is_trusted(&self, proxy_id: &ProxyId) -> bool {
+        let min_trust = f64::from_bits(self.min_trust.load(Ordering::Relaxed));
+        self.trust
+            .get(proxy_id)
+            .is_none_or(|e| e.score >= min_trust)
+    }
+
+    pub fn trust_scores(&self) -> Vec<(ProxyId, WorkerTrust)> {
+        self.trust
+            .iter()
+            .map(|e| (e.key().to_owned(), e.value().to_owned()))
+            .collect()
+    }
+
+    /// Gives a worker a clean slate, returns false when it had no trust recorded.
+    pub fn reset_trust<LoggingCTX: Serialize>(
+        &self,
+        proxy_id: &ProxyId,
+        logging_ctx: &LoggingCTX,
+    ) -> bool {
+        let reset = self.trust.remove(proxy_id).is_some();
+        info!(ctx:serde = logging_ctx; "reset the trust of {proxy_id}: {reset}");
+        reset
+    }
+
+    pub fn evict_expired_spot_checks<LoggingCTX: Serialize>(&self, logging_ctx: &LoggingCTX) {
+        let oldest = Utc::now().naive_utc() - SPOT_CHECK_TTL;
+        let before = self.pending_spot_checks.len();
+        self.pending_spot_checks
+            .retain(|_, e| e.sampled_at > oldest);
+        debug!(ctx:serde = logging_ctx; "evicted {} expired spot checks", before - self.pending_spot_checks.len());
+    }
+
+    /// Penalizes a worker once for all the results of one return that were
+    /// outside of its leased task.
+    pub fn record_unsolicited<LoggingCTX: Serialize>(
+        &self,
+        proxy_id: &ProxyId,
+        urls: &[Url],
+        logging_ctx: &LoggingCTX,
+    ) {
+        warn!(ctx:serde = logging_ctx; "proxy returned {} results which are not in its task", urls.len());
+        let mut trust = self.trust.entry(proxy_id.to_owned()).or_default();
+        trust.unsolicited_results += urls.len() as u64;
+        trust.score *= UNSOLICITED_PENALTY;
+    }
+
+    /// Settles the spot check of `http_res.url` when there is one from another
+    /// worker, otherwise maybe samples the result for a spot check. Returns the
+    /// req to hand out again when a spot check was sampled.
+    pub fn check<LoggingCTX: Serialize>(
+        &self,
+        proxy_id: &ProxyId,
+        http_res: &HttpRes,
+        req: &ProxyReq,
+        task_id: Uuid,
+        logging_ctx: &LoggingCTX,
+    ) -> (Verdict, Option<ProxyReq>) {
+        // only complete successful bodies are comparable
+        let comparable = (200..300).contains(&http_res.status) && !http_res.body.is_empty();
+
+        if let Some((_, pending)) = self
+            .pending_spot_checks
+            .remove_if(&http_res.url, |_, e| e.proxy_id != *proxy_id)
+        {
+            // a failed refetch says nothing about the worker that was checked
+            if comparable {
+                self.settle(&pending, text_simhash(http_res), &http_res.url, logging_ctx);
+            }
+            return (Verdict::SpotCheck, None);
+        }
+        // our own spot check came back to the same worker, it has to go out again
+        if self.pending_spot_checks.contains_key(&http_res.url) {
+            return (Verdict::SpotCheck, Some(req.to_owned()));
+        }
+
+        if !comparable
+            || self.pending_spot_checks.len() >= MAX_PENDING_SPOT_CHECKS
+            || !sampled(task_id, &http_res.url, self.spot_check_fraction())
+        {
+            return (Verdict::Accept, None);
+        }
+
+        debug!(ctx:serde = logging_ctx; "sampled {} for a spot check", http_res.url);
+        self.pending_spot_checks.insert(
+            http_res.url.to_owned(),
+            PendingSpotCheck {
+                proxy_id: proxy_id.to_owned(),
+                simhash: text_simhash(http_res),
+                sampled_at: Utc::now().naive_utc(),
+            },
+        );
+        (Verdict::Accept, Some(req.to_owned()))
+    }
+
+    fn settle<LoggingCTX: Serialize>(
+        &self,
+        pending: &PendingSpotCheck,
+        simhash: u64,
+        url: &Url,
+        logging_ctx: &LoggingCTX,
+    ) {
+        let mut trust = self.trust.entry(pending.proxy_id.to_owned()).or_default();
+        if (pending.simhash ^ simhash).count_ones() <= MAX_SPOT_CHECK_DISTANCE {
+            trust.spot_checks_passed += 1;
+            trust.score = (trust.score + MATCH_REWARD).min(1.0);
+            return;
+        }
+
+        trust.spot_checks_failed += 1;
+        trust.score *= MISMATCH_PENALTY;
+        info!(
+            ctx:serde = logging_ctx;
+            "spot check of {url} sampled at {} does not match what {} returned, trust is now {:.3}",
+            pending.sampled_at,
+            pending.proxy_id,
+            trust.score
+        );
+    }
+}
+
+impl Default for IntegrityChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn default_min_trust() -> AtomicU64 {
+    0.2f64.to_bits().into()
+}
+
+/// The simhash of the visible, case and whitespace normalized text, so markup
+/// like nonces and session ids that differ between fetches does not count.
+fn text_simhash(http_res: &HttpRes) -> u64 {
+    let body = String::from_utf8_lossy(&http_res.body);
+    let is_html = http_res
+        .content_type
+        .as_deref()
+        .is_none_or(|e| e.contains("html"));
+    let text = if is_html {
+        let html = Html::parse_document(&body);
+        html.root_element()
+            .descendants()
+            .filter_map(|node| {
+                let text = node.value().as_text()?;
+                let parent = node.parent()?.value().as_element()?;
+                let hidden = matches!(parent.name(), "script" | "style" | "noscript" | "template");
+                (!hidden).then(|| text.to_string())
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        body.into_owned()
+    };
+    let words = text
+        .split_whitespace()
+        .map(|e| e.to_lowercase())
+        .collect::<Vec<_>>();
+
+    simhash(&words)
+}
+
+// keyed on the task id so a worker can't tell up front which of its urls get checked
+fn sampled(task_id: Uuid, url: &Url, fraction: f64) -> bool {
+    if fraction <= 0.0 {
+        return false;
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(task_id.as_bytes());
+    hasher.update(url.as_str().as_bytes());
+    let hash = hasher.finalize();
+    let roll = u64::from_be_bytes(hash[..8].try_into().unwrap()) as f64 / u64::MAX as f64;
+
+    roll < fraction
+}
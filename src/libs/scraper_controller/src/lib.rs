@@ -1,11 +1,33 @@
 pub mod scraper_controller;
 pub use scraper_controller::ScraperController;
 
-mod proxy_id;
+pub mod proxy_id;
 pub use proxy_id::HEADER_KEY;
 pub use proxy_id::ProxyId;
 
 mod file_iterator_task_generator;
 pub use file_iterator_task_generator::FileIteratorTaskGenerator;
 
+mod urls_frontier_task_generator;
+pub use urls_frontier_task_generator::UrlsFrontierTaskGenerator;
+
+pub mod robots;
+pub use robots::RobotsCache;
+
+pub mod host_scheduler;
+pub use host_scheduler::HostScheduler;
+
+pub mod dead_letters;
+pub use dead_letters::DeadLetterStore;
+
+pub mod integrity;
+pub use integrity::IntegrityChecker;
+
+pub mod base64_body;
+
+pub mod simhash;
+
+pub mod worker_token;
+pub use worker_token::WorkerTokenSigner;
+
 // pub mod ipv4_iterator_task_generator;
@@ -1,12 +1,14 @@
 use std::{fmt::Display, ops::Deref};
 
-use http::HeaderMap;
+use http::{HeaderMap, header::AUTHORIZATION};
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Postgres};
 
-use exn::{OptionExt, Result, ResultExt};
+use exn::{Exn, OptionExt, Result, ResultExt};
 use utoipa::ToSchema;
 
+use crate::worker_token::WorkerTokenSigner;
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug, ToSchema)]
 pub struct ProxyId(String);
 
@@ -26,50 +28,82 @@ pub enum Error {
     #[error("No content in the proxy id header")]
     NoHeaderContent,
 
+    #[error("No bearer worker token in the authorization header")]
+    NoToken,
+
+    #[error("The worker token is invalid")]
+    InvalidToken,
+
+    #[error("The worker token was revoked")]
+    RevokedToken,
+
+    #[error("The proxy id is enrolled with another worker token")]
+    ForeignDevice,
+
     #[error("Failed to query db")]
     DBQuery,
 }
 
 impl ProxyId {
+    /// Authenticates a worker by its enrollment token. The proxy id itself stays
+    /// the `machine-id` header, a device gets bound to the first token it shows
+    /// up with so revoking a token locks out all of its devices.
     pub async fn from_http_headers(
         headers: &HeaderMap,
         db_pool: &Pool<Postgres>,
+        token_signer: &WorkerTokenSigner,
     ) -> Result<Self, Error> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|e| e.to_str().ok())
+            .and_then(|e| e.strip_prefix("Bearer "))
+            .ok_or_raise(|| Error::NoToken)?;
+        let claims = token_signer
+            .verify(token.trim())
+            .or_raise(|| Error::InvalidToken)?;
+
         let id = headers.get(HEADER_KEY).ok_or_raise(|| Error::NoHeader)?;
         let id = id.to_str().or_raise(|| Error::NoHeaderContent)?.to_owned();
+        if id.is_empty() {
+            return Err(Exn::new(Error::NoHeaderContent));
+        }
 
-        let device_exists = sqlx::query_scalar!(
+        // a token that is signed but gone from the table counts as revoked
+        let active = sqlx::query_scalar!(
             "
-             SELECT EXISTS (
-                SELECT 1 
-                FROM Devices 
-                WHERE machine_id = $1
-            ) AS row_exists;    
-        ",
-            &id
+                SELECT revoked_at IS NULL AS \"active!\"
+                FROM EnrollmentTokens
+                WHERE id = $1;
+            ",
+            claims.id
         )
-        .fetch_one(db_pool)
+        .fetch_optional(db_pool)
         .await
         .or_raise(|| Error::DBQuery)?
         .unwrap_or(false);
-
-        if device_exists {
-            return Ok(Self(id));
+        if !active {
+            return Err(Exn::new(Error::RevokedToken));
         }
 
-        sqlx::query!(
+        let enrolled_with = sqlx::query_scalar!(
             "
             INSERT INTO Devices
-                (machine_id)
+                (machine_id, enrollment_token_id)
             VALUES
-                ($1)
-            ;
+                ($1, $2)
+            ON CONFLICT (machine_id) DO UPDATE SET
+                enrollment_token_id = COALESCE(Devices.enrollment_token_id, EXCLUDED.enrollment_token_id)
+            RETURNING enrollment_token_id;
         ",
-            &id
+            &id,
+            claims.id
         )
-        .execute(db_pool)
+        .fetch_one(db_pool)
         .await
         .or_raise(|| Error::DBQuery)?;
+        if enrolled_with != Some(claims.id) {
+            return Err(Exn::new(Error::ForeignDevice));
+        }
 
         Ok(Self(id))
     }
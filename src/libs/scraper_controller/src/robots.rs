@@ -0,0 +1,334 @@
+use std::{collections::HashMap, time::Duration as StdDuration};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use dashmap::DashMap;
+use futures::stream::{self, StreamExt};
+use log::{debug, info};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::scraper_controller::{ProxyReq, ProxyTask};
+
+pub const ROBOTS_USER_AGENT: &str = "oxalate";
+
+const ROBOTS_TTL_HOURS: i64 = 24;
+const ROBOTS_UNREACHABLE_TTL_MINUTES: i64 = 30;
+const ROBOTS_FETCH_TIMEOUT_SECS: u64 = 10;
+const ROBOTS_MAX_BYTES: usize = 500 * 1024;
+const ROBOTS_FETCH_CONCURRENCY: usize = 32;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RobotsCache {
+    #[serde(skip)]
+    client: Client,
+    entries: DashMap<String, RobotsEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RobotsEntry {
+    pub fetched_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub rules: RobotsRules,
+    /// the robots.txt could not be fetched, the rules of the host are unknown
+    #[serde(default)]
+    pub unreachable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RobotsRules {
+    pub rules: Vec<RobotsRule>,
+    pub crawl_delay: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RobotsRule {
+    pub allow: bool,
+    pub pattern: String,
+}
+
+impl RobotsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops every request the robots.txt of its host disallows. Hosts that are
+    /// missing from the cache or whose entry expired are fetched first. The
+    /// requests of hosts whose robots.txt is unreachable are returned next to
+    /// the task, they have to wait until the rules are known.
+    pub async fn filter_task<LoggingCTX: Serialize + Send + Sync>(
+        &self,
+        task: ProxyTask,
+        logging_ctx: &LoggingCTX,
+    ) -> (ProxyTask, Vec<ProxyReq>) {
+        self.refresh_hosts(task.proxy_reqs.iter().map(|e| e.url()), logging_ctx)
+            .await;
+
+        let total = task.proxy_reqs.len();
+        let (known, unknown): (Vec<_>, Vec<_>) = task
+            .proxy_reqs
+            .into_iter()
+            .partition(|req| self.is_known(req.url()));
+        let proxy_reqs = known
+            .into_iter()
+            .filter(|req| self.is_allowed(req.url()))
+            .collect::<Box<[_]>>();
+
+        info!(
+            ctx:serde = logging_ctx;
+            "robots.txt filtered out {} of {} proxy reqs, {} wait for an unreachable robots.txt",
+            total - proxy_reqs.len() - unknown.len(), total, unknown.len()
+        );
+        (ProxyTask { proxy_reqs, ..task }, unknown)
+    }
+
+    pub fn is_allowed(&self, url: &Url) -> bool {
+        match self.entries.get(&robots_key(url)) {
+            Some(entry) => entry.rules.is_allowed(url),
+            None => true,
+        }
+    }
+
+    /// Whether the rules for `url` were fetched, hosts missing from the cache
+    /// or with an unreachable robots.txt are unknown.
+    pub fn is_known(&self, url: &Url) -> bool {
+        self.entries
+            .get(&robots_key(url))
+            .is_some_and(|entry| !entry.unreachable)
+    }
+
+    pub fn crawl_delay(&self, url: &Url) -> Option<f64> {
+        self.entries
+            .get(&robots_key(url))
+            .and_then(|entry| entry.rules.crawl_delay)
+    }
+
+    pub fn evict_expired(&self) {
+        let now = Utc::now().naive_utc();
+        self.entries.retain(|_, entry| entry.expires_at > now);
+    }
+
+    /// Fetches the robots.txt of every host of `urls` that is missing from the
+    /// cache or whose entry expired.
+    pub async fn refresh_hosts<'a, LoggingCTX: Serialize + Send + Sync>(
+        &self,
+        urls: impl IntoIterator<Item = &'a Url>,
+        logging_ctx: &LoggingCTX,
+    ) {
+        let now = Utc::now().naive_utc();
+        let mut stale_hosts = HashMap::new();
+        for url in urls {
+            let key = robots_key(url);
+            let is_fresh = self
+                .entries
+                .get(&key)
+                .is_some_and(|entry| entry.expires_at > now);
+            if !is_fresh {
+                stale_hosts.entry(key).or_insert_with(|| url.to_owned());
+            }
+        }
+
+        if stale_hosts.is_empty() {
+            return;
+        }
+
+        debug!(ctx:serde = logging_ctx; "fetching robots.txt for {} hosts", stale_hosts.len());
+        let fetched = stream::iter(stale_hosts)
+            .map(|(key, url)| async move { (key, self.fetch(&url).await) })
+            .buffer_unordered(ROBOTS_FETCH_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+
+        for (key, entry) in fetched {
+            self.entries.insert(key, entry);
+        }
+        debug!(ctx:serde = logging_ctx; "fetched robots.txt for all stale hosts");
+    }
+
+    async fn fetch(&self, url: &Url) -> RobotsEntry {
+        let now = Utc::now().naive_utc();
+        let ok_ttl = now + Duration::hours(ROBOTS_TTL_HOURS);
+        let unreachable_ttl = now + Duration::minutes(ROBOTS_UNREACHABLE_TTL_MINUTES);
+
+        let robots_url = match url.join("/robots.txt") {
+            Ok(e) => e,
+            Err(_) => {
+                return RobotsEntry {
+                    fetched_at: now,
+                    expires_at: ok_ttl,
+                    rules: RobotsRules::default(),
+                    unreachable: false,
+                };
+            }
+        };
+
+        let res = self
+            .client
+            .get(robots_url)
+            .header(reqwest::header::USER_AGENT, ROBOTS_USER_AGENT)
+            .timeout(StdDuration::from_secs(ROBOTS_FETCH_TIMEOUT_SECS))
+            .send()
+            .await;
+
+        // rfc 9309: 4xx means there are no restrictions, 5xx and network errors
+        // mean nothing may be crawled until the host recovers
+        let unreachable = RobotsEntry {
+            fetched_at: now,
+            expires_at: unreachable_ttl,
+            rules: RobotsRules::disallow_all(),
+            unreachable: true,
+        };
+        let rules = match res {
+            Ok(res) if res.status().is_success() => match res.bytes().await {
+                Ok(body) => {
+                    let body = &body[..body.len().min(ROBOTS_MAX_BYTES)];
+                    let body = String::from_utf8_lossy(body);
+                    RobotsRules::parse(&body, ROBOTS_USER_AGENT)
+                }
+                Err(_) => return unreachable,
+            },
+            Ok(res) if res.status().is_client_error() => RobotsRules::default(),
+            _ => return unreachable,
+        };
+
+        RobotsEntry {
+            fetched_at: now,
+            expires_at: ok_ttl,
+            rules,
+            unreachable: false,
+        }
+    }
+}
+
+impl RobotsRules {
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![RobotsRule {
+                allow: false,
+                pattern: "/".to_owned(),
+            }],
+            crawl_delay: None,
+        }
+    }
+
+    pub fn parse(body: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_lowercase();
+
+        let mut specific = None;
+        let mut wildcard = None;
+
+        let mut group_agents: Vec<String> = vec![];
+        let mut group = RobotsRules::default();
+        let mut in_rules = false;
+
+        let mut finish_group = |agents: &[String], group: RobotsRules| {
+            for agent in agents {
+                if agent == "*" {
+                    wildcard
+                        .get_or_insert_with(RobotsRules::default)
+                        .merge(&group);
+                } else if user_agent.contains(agent.as_str()) {
+                    specific
+                        .get_or_insert_with(RobotsRules::default)
+                        .merge(&group);
+                }
+            }
+        };
+
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        finish_group(&group_agents, std::mem::take(&mut group));
+                        group_agents.clear();
+                        in_rules = false;
+                    }
+                    group_agents.push(value.to_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    if value.is_empty() {
+                        continue;
+                    }
+                    group.rules.push(RobotsRule {
+                        allow: key == "allow",
+                        pattern: value.to_owned(),
+                    });
+                }
+                "crawl-delay" => {
+                    in_rules = true;
+                    group.crawl_delay = value
+                        .parse()
+                        .ok()
+                        .filter(|e: &f64| e.is_finite() && *e >= 0.0);
+                }
+                _ => {}
+            }
+        }
+        finish_group(&group_agents, group);
+
+        specific.or(wildcard).unwrap_or_default()
+    }
+
+    pub fn is_allowed(&self, url: &Url) -> bool {
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_owned(),
+        };
+        if path == "/robots.txt" {
+            return true;
+        }
+
+        // longest matching pattern wins, on a tie allow wins
+        self.rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, &path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    fn merge(&mut self, other: &RobotsRules) {
+        self.rules.extend(other.rules.iter().cloned());
+        if other.crawl_delay.is_some() {
+            self.crawl_delay = other.crawl_delay;
+        }
+    }
+}
+
+fn robots_key(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
+
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(e) => (e, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        let is_last = i == parts.len() - 1;
+        if is_last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
@@ -1,22 +1,33 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     error::Error as StdError,
     sync::{
         Arc,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
     },
 };
 
-use crate::ProxyId;
+use crate::{
+    ProxyId,
+    dead_letters::DeadLetterStore,
+    host_scheduler::HostScheduler,
+    integrity::{IntegrityChecker, Verdict},
+    robots::RobotsCache,
+};
 use async_trait::async_trait;
 use chrono::{Duration, NaiveDateTime, Utc};
 use dashmap::DashMap;
 use enum_dispatch::enum_dispatch;
 use exn::*;
-use log::{debug, info};
+use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 use url::Url;
 use utoipa::ToSchema;
+use uuid::Uuid;
+
+const MAX_EMPTY_TASK_RETRIES: usize = 8;
+const DEFAULT_TASK_LEASE_SECS: u64 = 900;
+const DEFAULT_MAX_REQ_ATTEMPTS: u32 = 3;
 
 #[enum_dispatch]
 #[async_trait]
@@ -31,13 +42,45 @@ pub trait ProxyTaskGenerator<Err: StdError + Send + Sync + 'static> {
 pub struct ScraperController {
     pub enabled: AtomicBool,
     pub active_tasks: DashMap<ProxyId, ActiveProxyTask>,
+
+    #[serde(default)]
+    pub robots_cache: RobotsCache,
+
+    #[serde(default)]
+    pub host_scheduler: HostScheduler,
+
+    #[serde(default)]
+    pub dead_letters: DeadLetterStore,
+
+    #[serde(default)]
+    pub integrity: IntegrityChecker,
+
+    #[serde(default = "default_task_lease_secs")]
+    task_lease_secs: AtomicU64,
+    #[serde(default = "default_max_req_attempts")]
+    max_req_attempts: AtomicU32,
 }
 
 #[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
 pub struct ProxyTask {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    /// set when the task is leased to a proxy, after it passes the reqs get requeued
+    #[serde(default)]
+    pub deadline: Option<NaiveDateTime>,
     pub proxy_reqs: Box<[ProxyReq]>,
 }
 
+impl ProxyTask {
+    pub fn new(proxy_reqs: Box<[ProxyReq]>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            deadline: None,
+            proxy_reqs,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
 pub enum ProxyReq {
     Http(HttpReq),
@@ -49,6 +92,91 @@ pub struct HttpReq {
     pub body: String,
     pub headers: HashMap<String, String>,
     pub method: HttpMethod,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub validators: HttpValidators,
+    #[serde(default)]
+    pub limits: FetchLimits,
+    /// how long the outlet waits after getting the task before sending the req,
+    /// keeps the reqs of one host a crawl-delay apart
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+/// Validators of the previous crawl, sent as `If-None-Match` and
+/// `If-Modified-Since` so an unchanged page comes back as a bodyless 304.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default)]
+pub struct HttpValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// What the outlet is willing to download for a req.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default)]
+pub struct FetchLimits {
+    /// bodies are cut off after this many bytes, `None` downloads everything
+    pub max_body_bytes: Option<u64>,
+    /// mime types without parameters, a trailing * matches every type with that
+    /// prefix. empty accepts every content type
+    pub accepted_mime_types: Vec<String>,
+}
+
+impl FetchLimits {
+    /// `max_body_bytes` of 0 means no limit, `accepted_mime_types` is comma separated.
+    pub fn from_config(max_body_bytes: u64, accepted_mime_types: &str) -> Self {
+        Self {
+            max_body_bytes: (max_body_bytes > 0).then_some(max_body_bytes),
+            accepted_mime_types: accepted_mime_types
+                .split(',')
+                .map(|e| e.trim().to_ascii_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect(),
+        }
+    }
+
+    /// A response without a content type is accepted, the parser sniffs it.
+    pub fn accepts_content_type(&self, content_type: Option<&str>) -> bool {
+        let Some(content_type) = content_type else {
+            return true;
+        };
+        if self.accepted_mime_types.is_empty() {
+            return true;
+        }
+
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.accepted_mime_types
+            .iter()
+            .any(|e| match e.strip_suffix('*') {
+                Some(prefix) => mime.starts_with(prefix),
+                None => *e == mime,
+            })
+    }
+}
+
+impl ProxyReq {
+    pub fn url(&self) -> &Url {
+        match self {
+            ProxyReq::Http(http_req) => &http_req.url,
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        match self {
+            ProxyReq::Http(http_req) => http_req.attempts,
+        }
+    }
+
+    fn set_attempts(&mut self, attempts: u32) {
+        match self {
+            ProxyReq::Http(http_req) => http_req.attempts = attempts,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
@@ -73,8 +201,74 @@ pub enum ProxyRes {
 pub struct HttpRes {
     pub url: Url,
     pub status: u16,
-    pub body: String,
+    /// the raw bytes, decoding them is up to the parser
+    #[serde(with = "crate::base64_body")]
+    #[schema(value_type = String, format = Byte)]
+    pub body: Vec<u8>,
     pub headers: HashMap<String, String>,
+    /// the declared Content-Type header, charset included
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub fetch_status: FetchStatus,
+    /// every redirect that was followed, in order. `url` stays the requested url
+    #[serde(default)]
+    pub redirects: Vec<RedirectHop>,
+}
+
+impl HttpRes {
+    /// The url the body came from, after following the redirects.
+    pub fn final_url(&self) -> &Url {
+        self.redirects
+            .last()
+            .map(|e| &e.location)
+            .unwrap_or(&self.url)
+    }
+
+    /// The target of a redirect the outlet did not follow bc it leaves the
+    /// origin of the req, it has to be crawled as a req of its own.
+    pub fn unfollowed_redirect(&self) -> Option<Url> {
+        if !matches!(self.status, 301 | 302 | 303 | 307 | 308) {
+            return None;
+        }
+        let location = self
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("location"))?
+            .1;
+        self.final_url().join(location).ok()
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct RedirectHop {
+    pub status: u16,
+    pub location: Url,
+}
+
+/// How much of the body the outlet downloaded.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FetchStatus {
+    #[default]
+    Complete,
+    /// the validators matched, there is no body
+    NotModified,
+    /// the body was cut off at the max body size
+    Truncated,
+    /// the content type is not accepted, the body was not downloaded
+    SkippedContentType,
+    /// the content length is above the max body size, the body was not downloaded
+    SkippedTooLarge,
+}
+
+/// The outputs of a completed task that are worth storing.
+#[derive(Debug)]
+pub struct CompletedTask {
+    pub task_id: Uuid,
+    pub accepted: Vec<ProxyRes>,
+    /// the reqs behind `accepted`, they have to be retried when storing the
+    /// outputs fails since the task is gone by then
+    pub accepted_reqs: Vec<ProxyReq>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
@@ -92,9 +286,26 @@ impl ScraperController {
         Self {
             enabled: false.into(),
             active_tasks: DashMap::new(),
+            robots_cache: RobotsCache::new(),
+            host_scheduler: HostScheduler::new(),
+            dead_letters: DeadLetterStore::new(),
+            integrity: IntegrityChecker::new(),
+            task_lease_secs: default_task_lease_secs(),
+            max_req_attempts: default_max_req_attempts(),
         }
     }
 
+    pub fn set_task_lease_secs(&self, lease_secs: u64) {
+        self.task_lease_secs
+            .store(lease_secs.max(1), Ordering::Relaxed);
+        self.host_scheduler.set_task_lease_secs(lease_secs.max(1));
+    }
+
+    pub fn set_max_req_attempts(&self, max_attempts: u32) {
+        self.max_req_attempts
+            .store(max_attempts.max(1), Ordering::Relaxed);
+    }
+
     pub fn enable(&self) {
         self.enabled.store(true, Ordering::Relaxed);
     }
@@ -126,21 +337,61 @@ impl ScraperController {
             return Ok(Some(active_task.value().task.to_owned()));
         }
 
-        let task = task_generator
-            .generate_task(logging_ctx)
-            .await
-            .or_raise(|| Error::TaskGeneratorFailed(std::any::type_name::<PTG>()))?;
+        // deferred reqs are only handed out once the robots.txt of their host is known
+        let ready_urls = self.host_scheduler.ready_urls();
+        self.robots_cache
+            .refresh_hosts(ready_urls.iter(), logging_ctx)
+            .await;
+
+        let mut task = None;
+        for _ in 0..MAX_EMPTY_TASK_RETRIES {
+            let generated_task = task_generator
+                .generate_task(logging_ctx)
+                .await
+                .or_raise(|| Error::TaskGeneratorFailed(std::any::type_name::<PTG>()))?;
+
+            let generator_done = generated_task.is_none();
+            let generated_task = match generated_task {
+                Some(e) => {
+                    let (task, unknown) = self.robots_cache.filter_task(e, logging_ctx).await;
+                    for req in unknown {
+                        self.host_scheduler.defer(req, logging_ctx);
+                    }
+                    task
+                }
+                None => {
+                    info!(ctx:serde = logging_ctx; "generator returned no task, only handing out deferred reqs");
+                    ProxyTask::new(Box::new([]))
+                }
+            };
+
+            let scheduled_task =
+                self.host_scheduler
+                    .schedule(generated_task, &self.robots_cache, logging_ctx);
+            if !scheduled_task.proxy_reqs.is_empty() {
+                task = Some(scheduled_task);
+                break;
+            }
+            if generator_done {
+                info!(ctx:serde = logging_ctx; "no task to hand out");
+                return Ok(None);
+            }
+            debug!(ctx:serde = logging_ctx; "the whole task was disallowed or deferred, generating another one");
+        }
 
-        let task = match task {
+        let mut task = match task {
             Some(e) => e,
             None => {
-                info!(ctx:serde = logging_ctx; "generator returned no task");
+                info!(ctx:serde = logging_ctx; "every generated task was disallowed by robots.txt or deferred");
                 return Ok(None);
             }
         };
-        let task = Arc::new(task);
 
         let now = Utc::now().naive_utc();
+        let lease = Duration::seconds(self.task_lease_secs.load(Ordering::Relaxed) as i64);
+        task.deadline = Some(now + lease);
+        let task = Arc::new(task);
+
         let active_task = ActiveProxyTask {
             created_at: now,
             last_reallocated: now,
@@ -154,46 +405,218 @@ impl ScraperController {
         Ok(Some(task))
     }
 
+    /// Marks every task whose lease ran out as dead and sends its reqs back to
+    /// the host scheduler, or to the dead letters once they ran out of attempts.
     pub async fn mark_dead_tasks<LoggingCTX: Serialize>(
         &self,
-        death_duration: &Duration,
         logging_ctx: &LoggingCTX,
     ) -> Box<[ProxyId]> {
         info!(ctx:serde = logging_ctx; "starting to mark dead tasks");
 
-        let mut dead_tasks_proxy_ids = vec![];
+        let now = Utc::now().naive_utc();
+        let mut dead_tasks = vec![];
         debug!(ctx:serde = logging_ctx; "getting active tasks mut iter lock");
         for mut task in self.active_tasks.iter_mut() {
-            let now = Utc::now().naive_utc();
-            if !task.dead && task.created_at + *death_duration > now {
+            let expired = task.task.deadline.is_some_and(|deadline| deadline <= now);
+            if !task.dead && expired {
                 debug!(ctx:serde = logging_ctx; "getting task mut value lock");
+                let proxy_id = task.key().to_owned();
                 let mut_active_task = task.value_mut();
                 mut_active_task.dead = true;
-                dead_tasks_proxy_ids.push(task.key().to_owned());
+                dead_tasks.push((proxy_id, mut_active_task.task.to_owned()));
             }
         }
 
-        info!(ctx:serde = logging_ctx; "marked dead tasks");
-        dead_tasks_proxy_ids.into_boxed_slice()
+        // requeue outside of the iter_mut so no shard lock is held meanwhile
+        for (proxy_id, task) in dead_tasks.iter() {
+            for req in task.proxy_reqs.iter() {
+                self.retry_req(
+                    req.to_owned(),
+                    "task lease expired",
+                    task.id,
+                    proxy_id,
+                    logging_ctx,
+                );
+            }
+        }
+
+        info!(ctx:serde = logging_ctx; "marked {} dead tasks", dead_tasks.len());
+        dead_tasks
+            .into_iter()
+            .map(|(proxy_id, _)| proxy_id)
+            .collect()
     }
 
+    /// Takes the results of a proxy's task and returns the ones worth storing.
+    /// Results for urls outside of its leased task are dropped, and so is
+    /// everything from a proxy whose trust fell below the minimum.
     pub async fn mark_task_as_complete<LoggingCTX: Serialize>(
         &self,
         proxy_id: &ProxyId,
-        _proxy_res: &[ProxyRes],
+        proxy_res: Vec<ProxyRes>,
         logging_ctx: &LoggingCTX,
-    ) -> Result<(), Error> {
+    ) -> Result<Option<CompletedTask>, Error> {
         info!(ctx:serde = logging_ctx; "called complete task at scraper controller");
 
-        if self.active_tasks.get(proxy_id).is_none() {
+        // a late or replayed return looks the same as a forged one here, so it
+        // is dropped without costing the proxy any trust
+        let Some((_, active_task)) = self.active_tasks.remove(proxy_id) else {
             info!(ctx:serde = logging_ctx; "A proxy tried to send a task output without having a task assigned");
-            return Ok(());
+            return Ok(None);
+        };
+
+        if !self.integrity.is_trusted(proxy_id) {
+            warn!(ctx:serde = logging_ctx; "rejecting the outputs of an untrusted proxy, its reqs are retried");
+            if !active_task.dead {
+                for req in active_task.task.proxy_reqs.iter() {
+                    self.retry_req(
+                        req.to_owned(),
+                        "untrusted proxy",
+                        active_task.task.id,
+                        proxy_id,
+                        logging_ctx,
+                    );
+                }
+            }
+            return Ok(None);
         }
 
-        self.active_tasks.remove(proxy_id);
+        let reqs = active_task
+            .task
+            .proxy_reqs
+            .iter()
+            .map(|e| (e.url(), e))
+            .collect::<HashMap<_, _>>();
+
+        let mut accepted = Vec::with_capacity(proxy_res.len());
+        let mut accepted_reqs = Vec::with_capacity(proxy_res.len());
+        let mut succeeded = HashSet::new();
+        let mut spot_checks = vec![];
+        let mut unsolicited = vec![];
+        for res in proxy_res {
+            let ProxyRes::HttpRes(ref http_res) = res;
+            let Some(req) = reqs.get(&http_res.url) else {
+                unsolicited.push(http_res.url.to_owned());
+                continue;
+            };
+
+            let retryable = http_res.status == 429 || http_res.status >= 500;
+            if !retryable {
+                succeeded.insert(http_res.url.to_owned());
+            }
+
+            let (verdict, spot_check) =
+                self.integrity
+                    .check(proxy_id, http_res, req, active_task.task.id, logging_ctx);
+            spot_checks.extend(spot_check);
+            if verdict == Verdict::Accept {
+                accepted_reqs.push((*req).to_owned());
+                accepted.push(res);
+            }
+        }
+
+        if !unsolicited.is_empty() {
+            self.integrity
+                .record_unsolicited(proxy_id, &unsolicited, logging_ctx);
+        }
+
+        // spot checks go through the host scheduler like any deferred req, so
+        // another proxy most likely picks them up
+        for req in spot_checks {
+            self.host_scheduler.defer(req, logging_ctx);
+        }
+
+        // a dead task already had its reqs requeued by mark_dead_tasks
+        if active_task.dead {
+            info!(ctx:serde = logging_ctx; "late output for a dead task, its reqs were already requeued");
+            return Ok(Some(CompletedTask {
+                task_id: active_task.task.id,
+                accepted,
+                accepted_reqs: vec![],
+            }));
+        }
 
-        info!(ctx:serde = logging_ctx; "completed and saved task");
-        Ok(())
+        let mut retried = 0;
+        for req in active_task.task.proxy_reqs.iter() {
+            if succeeded.contains(req.url()) {
+                continue;
+            }
+            self.retry_req(
+                req.to_owned(),
+                "no successful response",
+                active_task.task.id,
+                proxy_id,
+                logging_ctx,
+            );
+            retried += 1;
+        }
+
+        info!(ctx:serde = logging_ctx; "completed task, {} reqs are retried", retried);
+        Ok(Some(CompletedTask {
+            task_id: active_task.task.id,
+            accepted,
+            accepted_reqs,
+        }))
+    }
+
+    /// Retries the reqs of a completed task whose outputs could not be stored.
+    pub fn retry_unstored<LoggingCTX: Serialize>(
+        &self,
+        proxy_id: &ProxyId,
+        task_id: Uuid,
+        reqs: Vec<ProxyReq>,
+        logging_ctx: &LoggingCTX,
+    ) {
+        warn!(ctx:serde = logging_ctx; "retrying {} reqs whose outputs could not be stored", reqs.len());
+        for req in reqs {
+            self.retry_req(
+                req,
+                "failed to store the output",
+                task_id,
+                proxy_id,
+                logging_ctx,
+            );
+        }
+    }
+
+    /// Puts the given dead letters, or all of them when `ids` is `None`, back in
+    /// the host scheduler with a fresh attempt count.
+    pub fn replay_dead_letters<LoggingCTX: Serialize>(
+        &self,
+        ids: Option<&[Uuid]>,
+        logging_ctx: &LoggingCTX,
+    ) -> usize {
+        let letters = self.dead_letters.take(ids);
+        let replayed = letters.len();
+        for letter in letters {
+            let mut req = letter.req;
+            req.set_attempts(0);
+            self.host_scheduler.defer(req, logging_ctx);
+        }
+
+        info!(ctx:serde = logging_ctx; "replayed {} dead letters", replayed);
+        replayed
+    }
+
+    fn retry_req<LoggingCTX: Serialize>(
+        &self,
+        mut req: ProxyReq,
+        reason: &str,
+        task_id: Uuid,
+        proxy_id: &ProxyId,
+        logging_ctx: &LoggingCTX,
+    ) {
+        let attempts = req.attempts() + 1;
+        req.set_attempts(attempts);
+
+        if attempts >= self.max_req_attempts.load(Ordering::Relaxed) {
+            debug!(ctx:serde = logging_ctx; "proxy req ran out of attempts, moving it to the dead letters");
+            self.dead_letters
+                .insert(req, reason, task_id, proxy_id, logging_ctx);
+            return;
+        }
+
+        self.host_scheduler.defer(req, logging_ctx);
     }
 }
 
@@ -203,6 +626,14 @@ impl Default for ScraperController {
     }
 }
 
+fn default_task_lease_secs() -> AtomicU64 {
+    DEFAULT_TASK_LEASE_SECS.into()
+}
+
+fn default_max_req_attempts() -> AtomicU32 {
+    DEFAULT_MAX_REQ_ATTEMPTS.into()
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Scraper constroller failed to generate a task from task generator: {0}")]
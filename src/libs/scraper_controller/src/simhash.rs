@@ -0,0 +1,39 @@
+/// Words per shingle, short texts fall back to single words.
+const SHINGLE_SIZE: usize = 3;
+
+/// The simhash of the word shingles of a text. Texts whose simhashes differ in
+/// only a few bits are near-duplicates. Shared by the parser's page
+/// fingerprints and the spot checks of worker results.
+pub fn simhash(words: &[String]) -> u64 {
+    let mut weights = [0i64; 64];
+    let mut add = |feature: &str| {
+        let hash = fnv1a(feature.as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if (hash >> bit) & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    };
+
+    if words.len() < SHINGLE_SIZE {
+        words.iter().for_each(|e| add(e));
+    } else {
+        words.windows(SHINGLE_SIZE).for_each(|e| add(&e.join(" ")));
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | (1 << bit))
+}
+
+/// A stable 64 bit hash, std's hasher may change between releases and the
+/// simhashes are stored.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use log::{debug, info};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use url::Url;
+
+use crate::scraper_controller::{
+    FetchLimits, HttpMethod, HttpReq, HttpValidators, ProxyReq, ProxyTask, ProxyTaskGenerator,
+};
+use thiserror::Error;
+
+use exn::{Result, ResultExt};
+
+/// Leases never scanned, stale or due urls from the `Urls` table, the crawl frontier
+/// that every parsed page feeds with its outgoing links.
+#[derive(Serialize, Debug)]
+pub struct UrlsFrontierTaskGenerator {
+    #[serde(skip)]
+    db_pool: Pool<Postgres>,
+    job_size: i64,
+    #[serde(skip)]
+    rescan_after: Duration,
+    limits: FetchLimits,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to lease urls from the Urls table")]
+    LeaseUrls,
+}
+
+impl UrlsFrontierTaskGenerator {
+    pub fn new(
+        db_pool: Pool<Postgres>,
+        job_size: usize,
+        rescan_after: Duration,
+        limits: FetchLimits,
+    ) -> Self {
+        Self {
+            db_pool,
+            job_size: job_size as i64,
+            rescan_after,
+            limits,
+        }
+    }
+}
+
+#[async_trait]
+impl ProxyTaskGenerator<Error> for UrlsFrontierTaskGenerator {
+    async fn generate_task<LoggingCTX: Serialize + Send + Sync>(
+        &self,
+        logging_ctx: &LoggingCTX,
+    ) -> Result<Option<ProxyTask>, Error> {
+        let now = Utc::now().naive_utc();
+        let stale_before = now - self.rescan_after;
+
+        // the lease and the last_scanned bump happen in one statement, SKIP LOCKED
+        // keeps concurrent get_task calls from handing out the same rows twice.
+        // urls the parser scheduled are due at next_scan_at, the lease clears it so a
+        // failed fetch falls back to the rescan_after rule
+        let leased = sqlx::query!(
+            r#"
+                WITH leased AS (
+                    SELECT url
+                    FROM Urls
+                    WHERE (next_scan_at IS NULL AND (last_scanned IS NULL OR last_scanned < $1))
+                        OR next_scan_at <= $3
+                    ORDER BY last_scanned ASC NULLS FIRST, created_at ASC
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+                UPDATE Urls
+                SET last_scanned = $3, next_scan_at = NULL
+                FROM leased
+                WHERE Urls.url = leased.url
+                RETURNING Urls.url, Urls.etag, Urls.last_modified;
+            "#,
+            stale_before,
+            self.job_size,
+            now,
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .or_raise(|| Error::LeaseUrls)?;
+
+        if leased.is_empty() {
+            debug!(
+                ctx:serde = logging_ctx;
+                "Urls frontier task generator is done; no unscanned or stale urls left"
+            );
+            return Ok(None);
+        }
+
+        let reqs = leased
+            .into_iter()
+            .filter_map(|e| {
+                let url = Url::parse(&e.url).ok()?;
+
+                let http_req = HttpReq {
+                    url,
+                    body: String::new(),
+                    headers: HashMap::new(),
+                    method: HttpMethod::Get,
+                    attempts: 0,
+                    // conditional GET, an unchanged page comes back as a bodyless 304
+                    validators: HttpValidators {
+                        etag: e.etag,
+                        last_modified: e.last_modified,
+                    },
+                    limits: self.limits.to_owned(),
+                    delay_ms: 0,
+                };
+
+                Some(ProxyReq::Http(http_req))
+            })
+            .collect();
+
+        let task = ProxyTask::new(reqs);
+
+        info!(
+            ctx:serde = logging_ctx;
+            "Successfully leased a new proxy task from the urls frontier!"
+        );
+        Ok(Some(task))
+    }
+}
@@ -0,0 +1,102 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use exn::{Exn, OptionExt, Result, ResultExt};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HMAC-SHA256 keys shorter than its output weaken the signatures.
+const MIN_SECRET_BYTES: usize = 32;
+/// The placeholder of deploy/.env.example, it must never sign real tokens.
+const EXAMPLE_SECRET: &str = "change-me";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("the worker token is malformed")]
+    Malformed,
+
+    #[error("the worker token has a bad signature")]
+    BadSignature,
+
+    #[error("the worker token expired")]
+    Expired,
+
+    #[error(
+        "the worker token secret has to be a random value of at least {MIN_SECRET_BYTES} bytes"
+    )]
+    WeakSecret,
+}
+
+/// Signs and verifies the enrollment tokens workers authenticate with. A token
+/// is `<id>.<expires_at>.<signature>`, the signature is an HMAC-SHA256 of the
+/// first two parts. Revocation lives in the `EnrollmentTokens` table.
+pub struct WorkerTokenSigner {
+    secret: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerTokenClaims {
+    pub id: Uuid,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl WorkerTokenSigner {
+    /// Refuses empty, short and example secrets, anyone could forge tokens with those.
+    pub fn new(secret: &str) -> Result<Self, Error> {
+        if secret.len() < MIN_SECRET_BYTES || secret == EXAMPLE_SECRET {
+            return Err(Exn::new(Error::WeakSecret));
+        }
+
+        Ok(Self {
+            secret: secret.as_bytes().to_vec(),
+        })
+    }
+
+    pub fn sign(&self, claims: &WorkerTokenClaims) -> String {
+        let expires_at = claims
+            .expires_at
+            .map(|e| e.and_utc().timestamp())
+            .unwrap_or(0);
+        let payload = format!("{}.{expires_at}", claims.id);
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes());
+
+        format!("{payload}.{signature}")
+    }
+
+    pub fn verify(&self, token: &str) -> Result<WorkerTokenClaims, Error> {
+        let (payload, signature) = token.rsplit_once('.').ok_or_raise(|| Error::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .or_raise(|| Error::Malformed)?;
+        // verify_slice compares in constant time
+        self.mac(payload)
+            .verify_slice(&signature)
+            .or_raise(|| Error::BadSignature)?;
+
+        let (id, expires_at) = payload.split_once('.').ok_or_raise(|| Error::Malformed)?;
+        let id = Uuid::parse_str(id).or_raise(|| Error::Malformed)?;
+        let expires_at = match expires_at.parse::<i64>().or_raise(|| Error::Malformed)? {
+            0 => None,
+            secs => Some(
+                DateTime::from_timestamp(secs, 0)
+                    .ok_or_raise(|| Error::Malformed)?
+                    .naive_utc(),
+            ),
+        };
+
+        if expires_at.is_some_and(|e| e <= Utc::now().naive_utc()) {
+            return Err(Exn::new(Error::Expired));
+        }
+
+        Ok(WorkerTokenClaims { id, expires_at })
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("hmac takes keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}